@@ -0,0 +1,178 @@
+//! Generates terrain without a window, for baking levels on machines without a display.
+//! Build it with `--no-default-features` to leave out the editor and its system libraries:
+//! `cargo build --release --bin terrain-cli --no-default-features`
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use bevy::prelude::*;
+use terrain_procgen::generation::{
+    export_terrain, generate_chunks, save_world, ChunkMesh, DensitySettings, ExportFormat,
+    ExportOptions, Preset, TerrainGeneratorConfig,
+};
+
+const USAGE: &str = "\
+Usage: terrain-cli [OPTIONS]
+
+Options:
+  -c, --config <FILE>  Config of the generator in RON, or TOML with a .toml extension
+  -p, --preset <NAME>  Start from a bundled preset: plains, mountains, caves or islands
+  -s, --seed <SEED>    Replace the seed of the config
+  -o, --output <FILE>  Export the meshes, the format follows the extension: obj, ply, stl or glb
+      --ascii          Write PLY files as text
+      --colors         Write vertex colors into PLY files
+      --per-chunk      One object per chunk in OBJ files, one file per chunk for PLY and STL
+  -w, --world <FILE>   Save the sampled chunks into a world file
+  -h, --help           Print this help";
+
+#[derive(Debug, Default)]
+struct Args {
+    config: Option<PathBuf>,
+    preset: Option<Preset>,
+    seed: Option<u32>,
+    output: Option<PathBuf>,
+    ascii: bool,
+    colors: bool,
+    per_chunk: bool,
+    world: Option<PathBuf>,
+}
+
+impl Args {
+    /// `None` when the help was asked for
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value of {arg}"));
+            match arg.as_str() {
+                "-c" | "--config" => parsed.config = Some(value()?.into()),
+                "-p" | "--preset" => {
+                    let name = value()?;
+                    parsed.preset =
+                        Some(Preset::from_name(&name).ok_or(format!("unknown preset {name}"))?);
+                }
+                "-s" | "--seed" => {
+                    let seed = value()?;
+                    parsed.seed = Some(seed.parse().map_err(|_| format!("invalid seed {seed}"))?);
+                }
+                "-o" | "--output" => parsed.output = Some(value()?.into()),
+                "-w" | "--world" => parsed.world = Some(value()?.into()),
+                "--ascii" => parsed.ascii = true,
+                "--colors" => parsed.colors = true,
+                "--per-chunk" => parsed.per_chunk = true,
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        if parsed.config.is_some() && parsed.preset.is_some() {
+            return Err("pass either --config or --preset".to_string());
+        }
+        if parsed.output.is_none() && parsed.world.is_none() {
+            return Err("nothing to write, pass --output or --world".to_string());
+        }
+        Ok(Some(parsed))
+    }
+
+    fn export_options(&self, output: &Path) -> Result<ExportOptions, String> {
+        let extension = output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let format = match extension.to_lowercase().as_str() {
+            "obj" => ExportFormat::Obj,
+            "ply" if self.ascii => ExportFormat::PlyAscii,
+            "ply" => ExportFormat::PlyBinary,
+            "stl" => ExportFormat::Stl,
+            "glb" => ExportFormat::Glb,
+            _ => return Err(format!("unknown mesh format of {}", output.display())),
+        };
+        Ok(ExportOptions {
+            format,
+            per_chunk: self.per_chunk,
+            vertex_colors: self.colors,
+        })
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let mut config = match (&args.config, args.preset) {
+        (Some(path), _) => TerrainGeneratorConfig::load(path)?,
+        (None, Some(preset)) => preset.config(),
+        (None, None) => TerrainGeneratorConfig::default(),
+    };
+    if let Some(seed) = args.seed {
+        config.seed = seed;
+    }
+    // Check the output before spending time on the chunks
+    let export_options = args
+        .output
+        .as_ref()
+        .map(|output| args.export_options(output))
+        .transpose()?;
+
+    if config.density == DensitySettings::Custom {
+        return Err("custom density functions can only be set from code".into());
+    }
+    let density = config
+        .density
+        .build(config.seed)?
+        .expect("only custom densities have no function");
+
+    let amount = config.chunks_amount;
+    println!(
+        "Generating {} chunks with seed {}",
+        amount.x * amount.y * amount.z,
+        config.seed
+    );
+    let chunks = generate_chunks(&config, density.0.as_ref());
+
+    for path in args.output.iter().chain(&args.world) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    if let (Some(output), Some(options)) = (&args.output, export_options) {
+        let meshes: Vec<_> = chunks
+            .iter()
+            .map(|(chunk, mesh)| ChunkMesh {
+                coord: chunk.coord(),
+                transform: Transform::IDENTITY,
+                mesh,
+            })
+            .collect();
+        for file in export_terrain(output, &meshes, options)? {
+            println!("Wrote {}", file.display());
+        }
+    }
+    if let Some(world) = &args.world {
+        save_world(world, &config, chunks.iter().map(|(chunk, _)| chunk))?;
+        println!("Wrote {}", world.display());
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
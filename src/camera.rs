@@ -3,13 +3,14 @@ use bevy::{
     prelude::*,
     window::PrimaryWindow,
 };
+use terrain_procgen::generation::TerrainViewer;
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_camera)
-            .add_systems(Update, pan_orbit_camera);
+            .add_systems(Update, (pan_orbit_camera, update_terrain_viewer));
     }
 }
 
@@ -127,6 +128,15 @@ fn pan_orbit_camera(
     ev_motion.clear();
 }
 
+/// Stream terrain around the point the camera orbits
+fn update_terrain_viewer(
+    mut query: Query<(&PanOrbitCamera, &mut TerrainViewer), Changed<PanOrbitCamera>>,
+) {
+    for (pan_orbit, mut viewer) in query.iter_mut() {
+        viewer.focus = Some(pan_orbit.focus);
+    }
+}
+
 /// Spawn a camera like this
 fn spawn_camera(mut commands: Commands) {
     let translation = Vec3::new(-2.0, 2.5, 5.0);
@@ -141,5 +151,6 @@ fn spawn_camera(mut commands: Commands) {
             radius,
             ..Default::default()
         },
+        TerrainViewer::default(),
     ));
 }
@@ -1,49 +1,155 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, tasks::Task, utils::HashMap};
+use serde::{Deserialize, Serialize};
 
+pub mod coloring;
+pub mod config;
+pub mod density;
+pub mod editing;
+pub mod export;
+pub mod expression;
+pub mod graph;
+pub mod history;
+pub mod material;
+pub mod meshing;
+pub mod noise;
+pub mod save;
 mod systems;
 mod tables;
+pub mod triplanar;
 mod utils;
 
+pub use coloring::{ColorMode, ColorRamp, VertexColoring};
+pub use config::{ConfigFileError, ConfigFormat, Preset};
+pub use density::{DensityError, DensityFunction, DensitySettings, TerrainDensity};
+pub use editing::{BrushMode, TerrainEdit};
+pub use export::{export_terrain, ChunkMesh, ExportFormat, ExportOptions, ExportTerrainEvent};
+pub use history::{EditHistory, HistoryCommand};
+pub use material::{MaterialId, MaterialRules};
+pub use meshing::{mesh_chunk, MesherKind, NormalMode};
+pub use save::{load_world, save_world, LoadWorldEvent, SaveWorldEvent};
+pub use triplanar::{SplatLayer, SplatSettings, TriplanarMaterial};
+
 pub struct MarchingCubesTerrain;
 
 impl Plugin for MarchingCubesTerrain {
     fn build(&self, app: &mut App) {
         use systems::*;
-        app.init_resource::<TerrainGeneratorConfig>()
+        app.add_plugins(triplanar::TriplanarPlugin)
+            .init_resource::<ChunkMap>()
+            .init_resource::<StoredChunks>()
+            .init_resource::<EditHistory>()
             .insert_resource(Msaa::Sample4)
             .add_event::<GenerateTerrainEvent>()
-            .add_systems(Startup, light)
+            .add_event::<TerrainEdit>()
+            .add_event::<HistoryCommand>()
+            .add_event::<SaveWorldEvent>()
+            .add_event::<LoadWorldEvent>()
+            .add_event::<ExportTerrainEvent>()
+            .add_systems(Startup, (light, setup_material))
             .add_systems(
                 Update,
                 (
+                    load_world_file
+                        .run_if(on_event::<LoadWorldEvent>())
+                        .before(update_density)
+                        .before(update_material)
+                        .before(create_chunks),
+                    update_density
+                        .run_if(
+                            on_event::<GenerateTerrainEvent>()
+                                .or_else(on_event::<LoadWorldEvent>()),
+                        )
+                        .before(create_chunks),
+                    update_material.run_if(
+                        on_event::<GenerateTerrainEvent>().or_else(on_event::<LoadWorldEvent>()),
+                    ),
                     create_chunks.run_if(on_event::<GenerateTerrainEvent>()),
-                    appply_ground_function
-                        .run_if(IntoSystem::into_system(
-                            |new_chunks: Query<Entity, Added<TerrainChunk>>| !new_chunks.is_empty(),
-                        ))
+                    stream_chunks
+                        .run_if(|config: Res<TerrainGeneratorConfig>| config.streaming)
                         .after(create_chunks),
-                    generate_chunks
-                        .run_if(IntoSystem::into_system(
-                            |changed_chunks: Query<Entity, Changed<TerrainChunk>>| {
-                                !changed_chunks.is_empty()
-                            },
-                        ))
-                        .after(appply_ground_function),
+                    // Later systems mustn't queue commands for the despawned chunks
+                    apply_deferred
+                        .after(stream_chunks)
+                        .before(update_chunk_lods),
+                    update_chunk_lods.after(stream_chunks),
+                    apply_terrain_edits.after(update_chunk_lods),
+                    apply_history_commands
+                        .run_if(on_event::<HistoryCommand>())
+                        .after(apply_terrain_edits),
+                    spawn_chunk_tasks.after(apply_history_commands),
+                    apply_chunk_tasks.after(spawn_chunk_tasks),
                 ),
             )
-            .add_systems(Update, (draw_bounding_box, draw_mesh_normals));
+            .add_systems(
+                Update,
+                (draw_bounding_box, draw_chunk_lods, draw_mesh_normals),
+            )
+            .add_systems(
+                Update,
+                (
+                    save_world_file.run_if(on_event::<SaveWorldEvent>()),
+                    export_chunk_meshes.run_if(on_event::<ExportTerrainEvent>()),
+                )
+                    .after(apply_chunk_tasks),
+            );
+    }
+
+    /// Runs after the game inserted its resources, a [`TerrainDensity`] inserted by the game
+    /// is kept by generating with [`DensitySettings::Custom`] unless the game inserted a config too
+    fn finish(&self, app: &mut App) {
+        if app.world.contains_resource::<TerrainDensity>() {
+            if !app.world.contains_resource::<TerrainGeneratorConfig>() {
+                app.insert_resource(TerrainGeneratorConfig {
+                    density: DensitySettings::Custom,
+                    ..default()
+                });
+            }
+        } else {
+            app.init_resource::<TerrainDensity>();
+        }
+        app.init_resource::<TerrainGeneratorConfig>();
     }
 }
 
 // TODO: split config when it becomes too big
 // also split ui into sections to make modifications to parts of generation algorithm possible
 // without modifying everything
-#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+/// Fields missing from config files take their default values,
+/// so files written before a field was added still load
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TerrainGeneratorConfig {
+    /// Amount of chunks in each direction when streaming is disabled
     pub chunks_amount: UVec3,
     pub chunk_size: UVec3,
     pub cube_edge_length: f32,
     pub isolevel: f32,
+    pub mesher: MesherKind,
+    pub normals: NormalMode,
+    pub index_format: MeshIndexFormat,
+    /// Maximum number of finished chunk meshes inserted into the world every frame
+    pub chunks_per_frame: u32,
+    /// Endless world, chunks are spawned around [`TerrainViewer`]s instead of a fixed grid
+    pub streaming: bool,
+    /// Distance in chunks around the viewers within which chunks are spawned,
+    /// at most [`MAX_VIEW_RADIUS`]
+    pub view_radius: u32,
+    /// Amount of coarser detail levels, each one doubles the spacing of the previous,
+    /// zero disables level of detail
+    pub lod_levels: u32,
+    /// Distance in chunks from the viewers at which the first coarser level starts,
+    /// every next level starts twice as far
+    pub lod_distance: f32,
+    pub seed: u32,
+    pub density: DensitySettings,
+    /// Rules for the materials the density function doesn't choose
+    pub materials: MaterialRules,
+    pub coloring: VertexColoring,
+    pub splatting: SplatSettings,
+    /// Maximum amount of brush strokes that can be undone, an editor setting left out of files
+    #[serde(skip)]
+    pub history_size: usize,
+    #[serde(skip)]
     pub show_gizmos: bool,
 }
 
@@ -54,66 +160,377 @@ impl Default for TerrainGeneratorConfig {
             chunks_amount: UVec3::new(4, 4, 4),
             chunk_size: UVec3::new(4, 4, 4),
             isolevel: 0f32,
+            mesher: MesherKind::default(),
+            normals: NormalMode::default(),
+            index_format: MeshIndexFormat::default(),
+            chunks_per_frame: 8,
+            streaming: false,
+            view_radius: 4,
+            lod_levels: 0,
+            lod_distance: 2f32,
+            seed: 0,
+            density: DensitySettings::default(),
+            materials: MaterialRules::default(),
+            coloring: VertexColoring::default(),
+            splatting: SplatSettings::default(),
+            history_size: 32,
             show_gizmos: false,
         }
     }
 }
 
+/// Size of the mesh index buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MeshIndexFormat {
+    /// 16 bit indices unless the mesh has too many vertices for them
+    #[default]
+    Auto,
+    /// Always use 32 bit indices
+    U32,
+}
+
 #[derive(Event, Debug)]
 pub struct GenerateTerrainEvent;
 
+/// Chunks are streamed around entities with this component when streaming is enabled
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct TerrainViewer {
+    /// Point to stream chunks around instead of the entity's translation
+    pub focus: Option<Vec3>,
+}
+
+/// Spawned chunk entities by their coordinates
+#[derive(Resource, Debug, Default)]
+pub struct ChunkMap(pub HashMap<IVec3, Entity>);
+
+/// Largest view radius streaming uses, the chunks within it are looked up every frame
+pub const MAX_VIEW_RADIUS: u32 = 32;
+
+/// Layers of points sampled around every chunk, meshers look into the cubes
+/// of the neighbouring chunks, dual ones need two layers of them for normals
+const CHUNK_PADDING: u32 = 2;
+
 #[derive(Debug, Clone, Copy)]
 struct Point {
     /// Absolute position in the world
     position: Vec3,
     value: f32,
+    material: MaterialId,
 }
 
-#[derive(Component, Debug)]
-struct TerrainChunk {
+#[derive(Component, Debug, Clone)]
+pub struct TerrainChunk {
+    /// Chunk's coordinates in the chunk grid
+    coord: IVec3,
     /// Chunk's position in the world,
     /// the same as the position of its first point
     position: Vec3,
+    /// Level of detail, cubes are `2^lod` times bigger than `cube_edge_length`
+    lod: u32,
     /// Chunk's size measuring in cubes
     size: UVec3,
+    /// Size of a single cube in the world
+    cube_size: Vec3,
     /// Chunk's size measuring in points, each direction is bigger by one
+    /// and by the padding on both sides
     point_size: UVec3,
-    /// 1D array of points
+    /// 1D array of points, including the padding
     points: Vec<Point>,
+    /// The points were edited or loaded instead of sampled from the density function
+    modified: bool,
+    /// Modified points of another level of detail, they are put back
+    /// once the chunk returns to their level
+    stored: Option<Box<TerrainChunk>>,
+}
+
+/// Marks a chunk whose mesh is out of date
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct ChunkRebuild {
+    /// Sample the density function before meshing, otherwise points are up to date
+    resample: bool,
+}
+
+/// Edited chunks that left the view radius by their coordinates,
+/// they come back with their points instead of being resampled
+#[derive(Resource, Debug, Default)]
+struct StoredChunks(HashMap<IVec3, TerrainChunk>);
+
+/// Chunk being sampled and meshed in the background
+#[derive(Component, Debug)]
+struct ChunkTask {
+    task: Task<ChunkTaskResult>,
+    /// The task replaces the chunk's points with newly sampled ones
+    resample: bool,
+}
+
+#[derive(Debug)]
+struct ChunkTaskResult {
+    /// Sampled points, `None` if the chunk was only meshed
+    points: Option<Vec<Point>>,
+    mesh: Mesh,
+    /// The mesh has the attributes of the splatting material
+    splatting: bool,
+}
+
+/// Sample and mesh the grid of `chunks_amount` chunks without an app,
+/// the chunks are split between the available threads
+pub fn generate_chunks(
+    config: &TerrainGeneratorConfig,
+    density: &dyn DensityFunction,
+) -> Vec<(TerrainChunk, Mesh)> {
+    let amount = config.chunks_amount.as_ivec3();
+    let mut chunks = Vec::new();
+    for z in 0..amount.z {
+        for y in 0..amount.y {
+            for x in 0..amount.x {
+                let coord = IVec3::new(x, y, z);
+                chunks.push(TerrainChunk::new(
+                    coord,
+                    config.chunk_size,
+                    config.cube_edge_length,
+                ));
+            }
+        }
+    }
+
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let per_thread = chunks.len().div_ceil(threads).max(1);
+    let meshes: Vec<Mesh> = std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .chunks_mut(per_thread)
+            .map(|batch| {
+                scope.spawn(move || {
+                    batch
+                        .iter_mut()
+                        .map(|chunk| {
+                            chunk.sample(density, &config.materials);
+                            mesh_chunk(chunk, config, Some(density))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("chunk thread panicked"))
+            .collect()
+    });
+    chunks.into_iter().zip(meshes).collect()
+}
+
+/// Material of the chunks when splatting is disabled
+pub fn terrain_standard_material() -> StandardMaterial {
+    StandardMaterial {
+        // Tinted by the vertex colors of the terrain materials
+        base_color: Color::WHITE,
+        double_sided: true,
+        // cull_mode: None,
+        perceptual_roughness: 1f32,
+        metallic: 0f32,
+        reflectance: 0f32,
+        ..Default::default()
+    }
+}
+
+/// Materials of the chunk meshes, the triplanar one is used when splatting is enabled
+#[derive(Resource, Debug)]
+struct TerrainMaterial {
+    standard: Handle<StandardMaterial>,
+    triplanar: Handle<TriplanarMaterial>,
 }
 
 impl TerrainChunk {
-    fn new(position: Vec3, size: UVec3, cube_edge_size: f32) -> Self {
+    pub fn new(coord: IVec3, size: UVec3, cube_edge_size: f32) -> Self {
+        Self::with_lod(coord, size, cube_edge_size, 0)
+    }
+
+    /// Chunk covering the same space as a full detail chunk,
+    /// but with `2^lod` times fewer cubes in each direction
+    pub fn with_lod(coord: IVec3, full_size: UVec3, cube_edge_size: f32, lod: u32) -> Self {
+        let size = (full_size >> lod).max(UVec3::ONE);
+        // Positions are computed in whole grid units first
+        // so that points shared by neighbouring chunks match exactly
+        let origin = (coord * full_size.as_ivec3()).as_vec3();
+        let step = full_size.as_vec3() / size.as_vec3();
+
         // Add one to each dimension because we specify chunk size in cubes but we need last points
-        let point_size_x = 1 + size.x as usize;
-        let point_size_y = 1 + size.y as usize;
-        let point_size_z = 1 + size.z as usize;
-
-        let mut points = Vec::with_capacity(point_size_x * point_size_y * point_size_z);
-        for z in 0..point_size_z {
-            for y in 0..point_size_y {
-                for x in 0..point_size_x {
+        let point_size = size + UVec3::ONE + 2 * UVec3::splat(CHUNK_PADDING);
+        let padding = CHUNK_PADDING as i32;
+
+        let mut points = Vec::with_capacity(
+            point_size.x as usize * point_size.y as usize * point_size.z as usize,
+        );
+        for z in 0..point_size.z as i32 {
+            for y in 0..point_size.y as i32 {
+                for x in 0..point_size.x as i32 {
+                    let idx = IVec3::new(x, y, z) - padding;
                     points.push(Point {
-                        position: Vec3::new(
-                            x as f32 * cube_edge_size,
-                            y as f32 * cube_edge_size,
-                            z as f32 * cube_edge_size,
-                        ) + position,
+                        position: (origin + idx.as_vec3() * step) * cube_edge_size,
                         value: 0f32,
+                        material: MaterialId::default(),
                     });
                 }
             }
         }
 
         Self {
-            position,
+            coord,
+            position: origin * cube_edge_size,
+            lod,
             size,
-            point_size: UVec3::new(
-                point_size_x as u32,
-                point_size_y as u32,
-                point_size_z as u32,
-            ),
+            cube_size: step * cube_edge_size,
+            point_size,
             points,
+            modified: false,
+            stored: None,
+        }
+    }
+
+    pub fn coord(&self) -> IVec3 {
+        self.coord
+    }
+
+    pub fn lod(&self) -> u32 {
+        self.lod
+    }
+
+    /// Switch the chunk to another level of detail, returns whether its points need to be sampled.
+    /// Modified points are kept aside and put back once the chunk returns to their level,
+    /// until then sampling takes over the values of the points they share with the new level
+    pub fn set_lod(&mut self, lod: u32, full_size: UVec3, cube_edge_size: f32) -> bool {
+        if lod == self.lod {
+            return false;
+        }
+        let mut stored = self.stored.take();
+        let chunk = std::mem::replace(
+            self,
+            Self::with_lod(self.coord, full_size, cube_edge_size, lod),
+        );
+        if stored.is_none() && chunk.modified {
+            stored = Some(Box::new(chunk));
+        }
+        match stored {
+            Some(stored) if stored.lod == lod => {
+                *self = *stored;
+                false
+            }
+            stored => {
+                self.stored = stored;
+                true
+            }
+        }
+    }
+
+    /// Copy of the chunk to keep its edited or loaded points while it's despawned,
+    /// `None` if it has none. Points still waiting to be sampled aren't kept,
+    /// only the ones stored for another level of detail
+    fn edits(&self, sampled: bool) -> Option<TerrainChunk> {
+        if !sampled {
+            self.stored.as_deref().cloned()
+        } else if self.modified || self.stored.is_some() {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Opposite corners of the space covered by the chunk
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        (
+            self.position,
+            self.position + self.size.as_vec3() * self.cube_size,
+        )
+    }
+
+    /// Material of the point at `pos`, including the padding,
+    /// `None` if the chunk has no point there
+    pub fn material_at(&self, pos: Vec3) -> Option<MaterialId> {
+        let idx = ((pos - self.position) / self.cube_size).round().as_ivec3();
+        let min_idx = IVec3::splat(-(CHUNK_PADDING as i32));
+        let max_idx = self.size.as_ivec3() + CHUNK_PADDING as i32;
+        if idx.cmplt(min_idx).any() || idx.cmpgt(max_idx).any() {
+            return None;
+        }
+        let point = self.point(idx);
+        (point.position == pos).then_some(point.material)
+    }
+
+    /// Index of the point in `points`, coordinates are counted in cubes from
+    /// the chunk's position and can reach `CHUNK_PADDING` cubes outside of the chunk
+    fn point_index(&self, idx: IVec3) -> u32 {
+        let padded = (idx + CHUNK_PADDING as i32).as_uvec3();
+        utils::from_3D_to_1D_index(padded, self.point_size)
+    }
+
+    fn point(&self, idx: IVec3) -> Point {
+        self.points[self.point_index(idx) as usize]
+    }
+
+    /// Gradient of the point values, one sided differences are used at the outermost padding layer
+    fn point_gradient(&self, idx: IVec3) -> Vec3 {
+        let min_idx = IVec3::splat(-(CHUNK_PADDING as i32));
+        let max_idx = self.size.as_ivec3() + CHUNK_PADDING as i32;
+        let mut gradient = Vec3::ZERO;
+        for axis in 0..3 {
+            let mut offset = IVec3::ZERO;
+            offset[axis] = 1;
+            let before = (idx - offset).max(min_idx);
+            let after = (idx + offset).min(max_idx);
+            gradient[axis] = (self.point(after).value - self.point(before).value)
+                / ((after[axis] - before[axis]) as f32 * self.cube_size[axis]);
+        }
+        gradient
+    }
+
+    /// Sample the density function into every point of the chunk,
+    /// points the density function doesn't give a material to get one from `rules`
+    pub fn sample(&mut self, density: &dyn DensityFunction, rules: &MaterialRules) {
+        self.modified = false;
+        let materials: Vec<_> = self
+            .points
+            .iter_mut()
+            .map(|point| {
+                let (value, material) = density.sample_material(point.position);
+                point.value = value;
+                material
+            })
+            .collect();
+
+        // Slopes need the values of the neighbouring points. They are central differences
+        // up to one layer into the padding, so neighbouring chunks agree on the materials
+        // of every point they share there, which covers all points meshers read materials of
+        for (i, material) in materials.into_iter().enumerate() {
+            let material = material.unwrap_or_else(|| {
+                let idx = utils::from_1D_to_3D_index(i as u32, self.point_size).as_ivec3()
+                    - CHUNK_PADDING as i32;
+                let point = self.points[i];
+                let normal = self.point_gradient(idx).normalize_or_zero();
+                rules.material(point.position, normal, -point.value)
+            });
+            self.points[i].material = material;
+        }
+
+        if let Some(stored) = self.stored.take() {
+            self.restore_points(&stored);
+            self.stored = Some(stored);
+        }
+    }
+
+    /// Copy the values and materials of the points `other` has at the same positions
+    fn restore_points(&mut self, other: &TerrainChunk) {
+        let max_idx = other.point_size.as_ivec3() - 1;
+        for point in &mut self.points {
+            let local = (point.position - other.position) / other.cube_size;
+            let idx = local.round().as_ivec3() + CHUNK_PADDING as i32;
+            if idx.cmplt(IVec3::ZERO).any() || idx.cmpgt(max_idx).any() {
+                continue;
+            }
+            let other_point =
+                other.points[utils::from_3D_to_1D_index(idx.as_uvec3(), other.point_size) as usize];
+            if other_point.position == point.position {
+                point.value = other_point.value;
+                point.material = other_point.material;
+            }
         }
     }
 }
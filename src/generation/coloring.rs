@@ -0,0 +1,142 @@
+//! Vertex colors computed from the shape of the terrain
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Colors placed along some property of the terrain, interpolated between the stops
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorRamp {
+    /// Positions of the stops and their colors, sorted by position
+    pub stops: Vec<(f32, Color)>,
+}
+
+impl ColorRamp {
+    /// Linear color at `t`, values outside of the stops take the color of the closest one,
+    /// an empty ramp is transparent
+    pub fn sample(&self, t: f32) -> Vec4 {
+        let color = |idx: usize| Vec4::from(self.stops[idx].1.as_linear_rgba_f32());
+        let Some(next) = self.stops.iter().position(|&(position, _)| position > t) else {
+            return self.stops.len().checked_sub(1).map_or(Vec4::ZERO, color);
+        };
+        if next == 0 {
+            return color(0);
+        }
+        let (start, end) = (self.stops[next - 1].0, self.stops[next].0);
+        color(next - 1).lerp(color(next), (t - start) / (end - start))
+    }
+
+    /// Restore the order of the stops after they were edited
+    pub fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+}
+
+/// Source of the vertex colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorMode {
+    /// Colors of the terrain materials
+    #[default]
+    Materials,
+    /// Ramps over height, slope and curvature
+    Ramps,
+}
+
+/// Ramps are layered over each other in the order of the fields,
+/// the alpha of their colors decides how much of the layers below shows through
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VertexColoring {
+    pub mode: ColorMode,
+    /// Over the height in the world
+    pub height: ColorRamp,
+    /// Over the angle between the surface and the horizontal plane in degrees
+    pub slope: ColorRamp,
+    /// Over the mean curvature, positive on ridges and negative in valleys
+    pub curvature: ColorRamp,
+}
+
+impl Default for VertexColoring {
+    fn default() -> Self {
+        Self {
+            mode: ColorMode::default(),
+            height: ColorRamp {
+                stops: vec![
+                    (-4f32, Color::rgb(0.76, 0.7, 0.5)),
+                    (0f32, Color::rgb(0.3, 0.5, 0.3)),
+                    (8f32, Color::rgb(0.25, 0.4, 0.2)),
+                    (14f32, Color::rgb(0.5, 0.48, 0.45)),
+                    (18f32, Color::rgb(0.95, 0.95, 0.97)),
+                ],
+            },
+            slope: ColorRamp {
+                stops: vec![
+                    (30f32, Color::rgba(0.45, 0.43, 0.4, 0f32)),
+                    (50f32, Color::rgba(0.45, 0.43, 0.4, 1f32)),
+                ],
+            },
+            curvature: ColorRamp {
+                stops: vec![
+                    (-0.5f32, Color::rgba(0.1, 0.08, 0.05, 0.6)),
+                    (0f32, Color::rgba(0f32, 0f32, 0f32, 0f32)),
+                    (0.5f32, Color::rgba(1f32, 1f32, 1f32, 0.3)),
+                ],
+            },
+        }
+    }
+}
+
+impl VertexColoring {
+    /// Linear color of a vertex with the given normal and curvature
+    pub fn color(&self, position: Vec3, normal: Vec3, curvature: f32) -> [f32; 4] {
+        let slope = normal.y.clamp(-1f32, 1f32).acos().to_degrees();
+        let mut color = self.height.sample(position.y);
+        for layer in [self.slope.sample(slope), self.curvature.sample(curvature)] {
+            color = color.lerp(layer.truncate().extend(1f32), layer.w);
+        }
+        color.to_array()
+    }
+
+    /// Colors of all of the vertices, `indices` are triangles used to estimate the curvature
+    pub fn vertex_colors(
+        &self,
+        positions: &[Vec3],
+        normals: &[Vec3],
+        indices: &[u32],
+    ) -> Vec<[f32; 4]> {
+        let curvatures = vertex_curvatures(positions, normals, indices);
+        positions
+            .iter()
+            .zip(normals)
+            .zip(curvatures)
+            .map(|((&position, &normal), curvature)| self.color(position, normal, curvature))
+            .collect()
+    }
+}
+
+/// Mean curvature of every vertex estimated out of how the normals change
+/// along the triangle edges around it
+pub fn vertex_curvatures(positions: &[Vec3], normals: &[Vec3], indices: &[u32]) -> Vec<f32> {
+    let mut sums = vec![0f32; positions.len()];
+    let mut counts = vec![0u32; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let (a, b) = (triangle[a] as usize, triangle[b] as usize);
+            let edge = positions[b] - positions[a];
+            let length_squared = edge.length_squared();
+            if length_squared <= f32::EPSILON {
+                continue;
+            }
+            // Normals spreading apart along the edge mean a convex surface
+            let curvature = (normals[b] - normals[a]).dot(edge) / length_squared;
+            for vertex in [a, b] {
+                sums[vertex] += curvature;
+                counts[vertex] += 1;
+            }
+        }
+    }
+    sums.into_iter()
+        .zip(counts)
+        .map(|(sum, count)| if count > 0 { sum / count as f32 } else { 0f32 })
+        .collect()
+}
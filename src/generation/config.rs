@@ -0,0 +1,152 @@
+//! Config files in RON or TOML and the bundled presets, so setups can be shared in version control
+
+use std::{fmt, fs, io, path::Path};
+
+use super::TerrainGeneratorConfig;
+
+/// Text format of a config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Ron,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// TOML for `.toml` files, RON for everything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Ron,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigFileError {
+    Io(io::Error),
+    /// The text isn't a valid config in the file's format
+    Parse(String),
+    Serialize(String),
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigFileError::Io(err) => write!(f, "{err}"),
+            ConfigFileError::Parse(err) => write!(f, "invalid config: {err}"),
+            ConfigFileError::Serialize(err) => write!(f, "can't serialize the config: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {}
+
+impl From<io::Error> for ConfigFileError {
+    fn from(err: io::Error) -> Self {
+        ConfigFileError::Io(err)
+    }
+}
+
+impl TerrainGeneratorConfig {
+    pub fn from_text(text: &str, format: ConfigFormat) -> Result<Self, ConfigFileError> {
+        match format {
+            // Splat height ranges were written without `Some` before they became optional
+            ConfigFormat::Ron => ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+                .from_str(text)
+                .map_err(|err| err.to_string()),
+            ConfigFormat::Toml => toml::from_str(text).map_err(|err| err.to_string()),
+        }
+        .map_err(ConfigFileError::Parse)
+    }
+
+    /// Pretty printed text, meant to be read and edited by hand
+    pub fn to_text(&self, format: ConfigFormat) -> Result<String, ConfigFileError> {
+        match format {
+            ConfigFormat::Ron => {
+                let pretty = ron::ser::PrettyConfig::default().struct_names(false);
+                ron::ser::to_string_pretty(self, pretty)
+                    .map(|text| text + "\n")
+                    .map_err(|err| err.to_string())
+            }
+            ConfigFormat::Toml => toml::to_string_pretty(self).map_err(|err| err.to_string()),
+        }
+        .map_err(ConfigFileError::Serialize)
+    }
+
+    /// Read the config at `path`, the format follows the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        Self::from_text(&fs::read_to_string(path)?, ConfigFormat::from_path(path))
+    }
+
+    /// Take over the generation setup of `loaded`,
+    /// the editor settings config files leave out stay as they are
+    pub fn take_setup(&mut self, loaded: TerrainGeneratorConfig) {
+        *self = TerrainGeneratorConfig {
+            history_size: self.history_size,
+            show_gizmos: self.show_gizmos,
+            ..loaded
+        };
+    }
+
+    /// Write the config into `path`, the format follows the extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigFileError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_text(ConfigFormat::from_path(path))?)?;
+        Ok(())
+    }
+}
+
+/// Configs bundled with the crate, the sources are in `presets/`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Gentle hills of grass
+    Plains,
+    /// Ridged peaks with snow
+    Mountains,
+    /// Tunnels winding through the ground
+    Caves,
+    /// An island sinking into the sand towards the borders
+    Islands,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::Plains,
+        Preset::Mountains,
+        Preset::Caves,
+        Preset::Islands,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Plains => "plains",
+            Preset::Mountains => "mountains",
+            Preset::Caves => "caves",
+            Preset::Islands => "islands",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    /// RON source of the preset
+    pub fn source(self) -> &'static str {
+        match self {
+            Preset::Plains => include_str!("../../presets/plains.ron"),
+            Preset::Mountains => include_str!("../../presets/mountains.ron"),
+            Preset::Caves => include_str!("../../presets/caves.ron"),
+            Preset::Islands => include_str!("../../presets/islands.ron"),
+        }
+    }
+
+    pub fn config(self) -> TerrainGeneratorConfig {
+        TerrainGeneratorConfig::from_text(self.source(), ConfigFormat::Ron)
+            .unwrap_or_else(|err| panic!("bundled preset {} is invalid: {err}", self.name()))
+    }
+}
@@ -0,0 +1,203 @@
+use std::{fmt, sync::Arc};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    expression::{Expression, ExpressionError},
+    graph::{DensityGraph, GraphError},
+    material::MaterialId,
+    noise::NoiseSettings,
+};
+
+/// Scalar field that is sampled into every point of a chunk.
+/// Points with values below the isolevel are considered to be inside of the terrain
+pub trait DensityFunction: Send + Sync {
+    fn sample(&self, pos: Vec3) -> f32;
+
+    /// Value at `pos` along with its material,
+    /// `None` leaves the material to [`super::MaterialRules`]
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        (self.sample(pos), None)
+    }
+
+    /// Gradient at `pos` from central differences `step` apart,
+    /// functions that know it analytically can return it exactly
+    fn gradient(&self, pos: Vec3, step: f32) -> Vec3 {
+        let difference = |offset: Vec3| self.sample(pos + offset) - self.sample(pos - offset);
+        Vec3::new(
+            difference(Vec3::X * step),
+            difference(Vec3::Y * step),
+            difference(Vec3::Z * step),
+        ) / (2f32 * step)
+    }
+}
+
+/// Density function used when sampling newly created chunks
+#[derive(Resource, Clone)]
+pub struct TerrainDensity(pub Arc<dyn DensityFunction>);
+
+impl TerrainDensity {
+    pub fn new(density_function: impl DensityFunction + 'static) -> Self {
+        Self(Arc::new(density_function))
+    }
+}
+
+impl Default for TerrainDensity {
+    fn default() -> Self {
+        Self::new(Plane::default())
+    }
+}
+
+/// Density function that is built from [`super::TerrainGeneratorConfig`]
+/// every time the terrain is generated
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum DensitySettings {
+    /// Keep the density function that was inserted into [`TerrainDensity`]
+    Custom,
+    /// Flat ground at zero height
+    #[default]
+    Plane,
+    /// Fractal noise terrain
+    Noise(NoiseSettings),
+    /// Primitives combined with CSG operators
+    Graph(DensityGraph),
+    /// Math expression, see [`super::expression`] for the syntax
+    Expression {
+        source: String,
+        /// Value of the `t` variable
+        #[serde(default)]
+        t: f32,
+    },
+}
+
+/// Settings that can't be built into a density function
+#[derive(Debug, Clone, PartialEq)]
+pub enum DensityError {
+    Expression(ExpressionError),
+    Graph(GraphError),
+}
+
+impl fmt::Display for DensityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DensityError::Expression(err) => write!(f, "{err}"),
+            DensityError::Graph(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for DensityError {}
+
+impl From<ExpressionError> for DensityError {
+    fn from(err: ExpressionError) -> Self {
+        DensityError::Expression(err)
+    }
+}
+
+impl From<GraphError> for DensityError {
+    fn from(err: GraphError) -> Self {
+        DensityError::Graph(err)
+    }
+}
+
+impl DensitySettings {
+    /// Returns `None` for [`DensitySettings::Custom`]
+    pub fn build(&self, seed: u32) -> Result<Option<TerrainDensity>, DensityError> {
+        Ok(match self {
+            DensitySettings::Custom => None,
+            DensitySettings::Plane => Some(TerrainDensity::new(Plane::default())),
+            DensitySettings::Noise(settings) => Some(TerrainDensity::new(settings.build(seed))),
+            DensitySettings::Graph(graph) => Some(graph.build(seed)?),
+            DensitySettings::Expression { source, t } => {
+                let mut expression = Expression::parse(source, seed)?;
+                expression.t = *t;
+                Some(TerrainDensity::new(expression))
+            }
+        })
+    }
+}
+
+/// Infinite plane, everything on the opposite side of the normal is solid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    /// Distance from the origin along the normal
+    pub offset: f32,
+}
+
+impl Default for Plane {
+    fn default() -> Self {
+        Self {
+            normal: Vec3::Y,
+            offset: 0f32,
+        }
+    }
+}
+
+impl DensityFunction for Plane {
+    fn sample(&self, pos: Vec3) -> f32 {
+        pos.dot(self.normal.normalize()) - self.offset
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl DensityFunction for Sphere {
+    fn sample(&self, pos: Vec3) -> f32 {
+        pos.distance(self.center) - self.radius
+    }
+}
+
+/// Axis-aligned box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cuboid {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+}
+
+impl DensityFunction for Cuboid {
+    fn sample(&self, pos: Vec3) -> f32 {
+        let q = (pos - self.center).abs() - self.half_extents;
+        q.max(Vec3::ZERO).length() + q.max_element().min(0f32)
+    }
+}
+
+/// Line segment with a radius
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+}
+
+impl DensityFunction for Capsule {
+    fn sample(&self, pos: Vec3) -> f32 {
+        let segment = self.end - self.start;
+        let t = ((pos - self.start).dot(segment) / segment.length_squared().max(f32::EPSILON))
+            .clamp(0f32, 1f32);
+        pos.distance(self.start + segment * t) - self.radius
+    }
+}
+
+/// Ring lying in the XZ plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Torus {
+    pub center: Vec3,
+    /// Distance from the center to the middle of the tube
+    pub major_radius: f32,
+    /// Radius of the tube
+    pub minor_radius: f32,
+}
+
+impl DensityFunction for Torus {
+    fn sample(&self, pos: Vec3) -> f32 {
+        let p = pos - self.center;
+        let ring_distance = Vec2::new(p.x, p.z).length() - self.major_radius;
+        Vec2::new(ring_distance, p.y).length() - self.minor_radius
+    }
+}
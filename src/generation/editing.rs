@@ -0,0 +1,242 @@
+//! Sculpting the sampled terrain
+
+use bevy::prelude::*;
+
+use super::{
+    utils::{from_1D_to_3D_index, from_3D_to_1D_index},
+    TerrainChunk, CHUNK_PADDING,
+};
+
+/// Shape of the brush and what it does to the points under it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BrushMode {
+    /// Add or remove material with a soft spherical falloff
+    #[default]
+    Sphere,
+    /// Add or remove material evenly in an axis aligned cube
+    Cube,
+    /// Blend the points towards the average of their neighbours
+    Smooth,
+    /// Pull the surface towards the horizontal plane through the center
+    Flatten,
+}
+
+/// Modify the points of every chunk within `radius` of `center`, touched chunks are re-meshed
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct TerrainEdit {
+    pub center: Vec3,
+    /// Half of the cube's edge for [`BrushMode::Cube`]
+    pub radius: f32,
+    /// Positive values add material and negative ones remove it,
+    /// smoothing and flattening blend with the absolute value clamped to one
+    pub strength: f32,
+    pub mode: BrushMode,
+}
+
+impl TerrainEdit {
+    /// Influence of the brush on a point, zero outside of the brush
+    fn weight(&self, position: Vec3) -> f32 {
+        let offset = position - self.center;
+        match self.mode {
+            BrushMode::Cube => {
+                if offset.abs().max_element() <= self.radius {
+                    1f32
+                } else {
+                    0f32
+                }
+            }
+            BrushMode::Sphere | BrushMode::Smooth | BrushMode::Flatten => {
+                let t = offset.length_squared() / (self.radius * self.radius);
+                if t < 1f32 {
+                    (1f32 - t) * (1f32 - t)
+                } else {
+                    0f32
+                }
+            }
+        }
+    }
+
+    /// Whether the brush reaches into the space covered by the points of the chunk
+    fn overlaps(&self, chunk: &TerrainChunk) -> bool {
+        let (min, max) = chunk.bounds();
+        let padding = chunk.cube_size * CHUNK_PADDING as f32;
+        let closest = self.center.clamp(min - padding, max + padding);
+        // The sphere fits into the cube, so the cube's test works for both
+        (closest - self.center).abs().max_element() <= self.radius
+    }
+}
+
+impl TerrainChunk {
+    /// Apply the brush to the points of the chunk including the padding,
+    /// returns whether any of them changed
+    pub fn apply_edit(&mut self, edit: &TerrainEdit) -> bool {
+        // Points kept for another level of detail have to stay up to date
+        if let Some(stored) = &mut self.stored {
+            stored.edit_points(edit, |_, _, _| {});
+        }
+        self.edit_points(edit, |_, _, _| {})
+    }
+
+    /// Apply the brush and report the index, old and new value of every changed point
+    pub(super) fn edit_points(
+        &mut self,
+        edit: &TerrainEdit,
+        mut on_change: impl FnMut(u32, f32, f32),
+    ) -> bool {
+        if !edit.overlaps(self) {
+            return false;
+        }
+
+        // Smoothing reads the neighbours before any of them changed
+        let values: Vec<f32> = self.points.iter().map(|point| point.value).collect();
+        let max_idx = self.point_size.as_ivec3() - 1;
+        let neighbour_average = |idx: IVec3| {
+            let mut sum = 0f32;
+            for axis in 0..3 {
+                let mut offset = IVec3::ZERO;
+                offset[axis] = 1;
+                for neighbour in [idx - offset, idx + offset] {
+                    sum +=
+                        values[from_3D_to_1D_index(neighbour.as_uvec3(), self.point_size) as usize];
+                }
+            }
+            sum / 6f32
+        };
+
+        let mut touched = false;
+        for (i, point) in self.points.iter_mut().enumerate() {
+            let weight = edit.weight(point.position);
+            if weight <= 0f32 {
+                continue;
+            }
+            let idx = from_1D_to_3D_index(i as u32, self.point_size).as_ivec3();
+            // The neighbours of the outermost points are in other chunks,
+            // they get the values smoothed there from `copy_shared_points`
+            if edit.mode == BrushMode::Smooth && is_outermost(idx, max_idx) {
+                continue;
+            }
+            touched = true;
+
+            let blend = (edit.strength.abs() * weight).min(1f32);
+            let previous = point.value;
+            point.value = match edit.mode {
+                // Solid points are below the isolevel
+                BrushMode::Sphere | BrushMode::Cube => point.value - edit.strength * weight,
+                BrushMode::Smooth => {
+                    let average = neighbour_average(idx);
+                    point.value + (average - point.value) * blend
+                }
+                BrushMode::Flatten => {
+                    let plane = point.position.y - edit.center.y;
+                    point.value + (plane - point.value) * blend
+                }
+            };
+            on_change(i as u32, previous, point.value);
+        }
+        self.modified |= touched;
+        touched
+    }
+
+    /// Take over the values of the points shared with the outermost layer of the padding
+    /// from a neighbouring chunk of the same level of detail, which has all of their neighbours.
+    /// Keeps the copies of shared points equal after smoothing, returns whether any value changed
+    pub fn copy_shared_points(&mut self, neighbour: &TerrainChunk) -> bool {
+        self.copy_points(neighbour, |_, _, _| {})
+    }
+
+    /// Copy the shared points and report the index, old and new value of every changed one
+    pub(super) fn copy_points(
+        &mut self,
+        neighbour: &TerrainChunk,
+        mut on_change: impl FnMut(u32, f32, f32),
+    ) -> bool {
+        if neighbour.lod != self.lod || neighbour.coord == self.coord {
+            return false;
+        }
+
+        let max_idx = self.point_size.as_ivec3() - 1;
+        let neighbour_max_idx = neighbour.point_size.as_ivec3() - 1;
+        let mut changed = false;
+        for (i, point) in self.points.iter_mut().enumerate() {
+            let idx = from_1D_to_3D_index(i as u32, self.point_size).as_ivec3();
+            if !is_outermost(idx, max_idx) {
+                continue;
+            }
+            let local = (point.position - neighbour.position) / neighbour.cube_size;
+            let neighbour_idx = local.round().as_ivec3() + CHUNK_PADDING as i32;
+            if is_outermost(neighbour_idx, neighbour_max_idx)
+                || neighbour_idx.cmplt(IVec3::ZERO).any()
+                || neighbour_idx.cmpgt(neighbour_max_idx).any()
+            {
+                continue;
+            }
+            let shared = neighbour.points
+                [from_3D_to_1D_index(neighbour_idx.as_uvec3(), neighbour.point_size) as usize];
+            if shared.position != point.position || shared.value == point.value {
+                continue;
+            }
+            on_change(i as u32, point.value, shared.value);
+            point.value = shared.value;
+            changed = true;
+        }
+        self.modified |= changed;
+        changed
+    }
+
+    /// Value interpolated trilinearly between the points,
+    /// `None` outside of the space covered by the chunk's cubes
+    pub fn value_at(&self, pos: Vec3) -> Option<f32> {
+        let local = (pos - self.position) / self.cube_size;
+        if local.cmplt(Vec3::ZERO).any() || local.cmpgt(self.size.as_vec3()).any() {
+            return None;
+        }
+        let cube = local.floor().as_ivec3().min(self.size.as_ivec3() - 1);
+        let t = local - cube.as_vec3();
+        let value = |offset: IVec3| self.point(cube + offset).value;
+        // Exact at the points, so chunks read the same value at the points they share
+        let lerp = |a: f32, b: f32, t: f32| a * (1f32 - t) + b * t;
+        let along_x =
+            |y: i32, z: i32| lerp(value(IVec3::new(0, y, z)), value(IVec3::new(1, y, z)), t.x);
+        let along_y = |z: i32| lerp(along_x(0, z), along_x(1, z), t.y);
+        Some(lerp(along_y(0), along_y(1), t.z))
+    }
+}
+
+/// Whether the point is in the outermost layer of the padding,
+/// `idx` is counted in points from the first one of the padding
+fn is_outermost(idx: IVec3, max_idx: IVec3) -> bool {
+    idx.cmpeq(IVec3::ZERO).any() || idx.cmpeq(max_idx).any()
+}
+
+/// First point where the ray enters the terrain, the ray is marched in steps of `step`
+/// and the crossing is refined by bisection. `value_at` gives the terrain's value at a point,
+/// `None` is treated as empty space
+pub fn raycast_terrain(
+    ray: Ray,
+    max_distance: f32,
+    step: f32,
+    isolevel: f32,
+    value_at: impl Fn(Vec3) -> Option<f32>,
+) -> Option<Vec3> {
+    let solid = |distance: f32| value_at(ray.get_point(distance)).is_some_and(|v| v < isolevel);
+    let mut previous = 0f32;
+    let mut distance = 0f32;
+    while distance <= max_distance {
+        if solid(distance) {
+            // Rays starting inside of the terrain hit right away
+            let (mut outside, mut inside) = (previous, distance);
+            for _ in 0..16 {
+                let middle = (outside + inside) / 2f32;
+                if solid(middle) {
+                    inside = middle;
+                } else {
+                    outside = middle;
+                }
+            }
+            return Some(ray.get_point(inside));
+        }
+        previous = distance;
+        distance += step;
+    }
+    None
+}
@@ -0,0 +1,330 @@
+//! Export of chunk meshes to Wavefront OBJ, PLY, STL and binary glTF
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+
+mod glb;
+
+use super::terrain_standard_material;
+
+/// File format of the exported meshes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// Wavefront OBJ with normals
+    #[default]
+    Obj,
+    PlyAscii,
+    PlyBinary,
+    /// Binary STL, it has no vertex attributes besides positions
+    Stl,
+    /// Binary glTF with a node per chunk and the terrain's material
+    Glb,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Obj => "obj",
+            ExportFormat::PlyAscii | ExportFormat::PlyBinary => "ply",
+            ExportFormat::Stl => "stl",
+            ExportFormat::Glb => "glb",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// One object per chunk in OBJ files, one file per chunk for PLY and STL,
+    /// glTF files always have a node per chunk
+    pub per_chunk: bool,
+    /// Write the vertex colors into PLY files
+    pub vertex_colors: bool,
+}
+
+/// Export the meshed chunks into `path`, or files next to it when exporting per chunk,
+/// the extension is replaced by the format's one
+#[derive(Event, Debug, Clone)]
+pub struct ExportTerrainEvent {
+    pub path: PathBuf,
+    pub options: ExportOptions,
+}
+
+/// Mesh of a chunk and the transform of its entity
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkMesh<'a> {
+    pub coord: IVec3,
+    pub transform: Transform,
+    pub mesh: &'a Mesh,
+}
+
+/// Triangle list of a chunk mesh in world space
+#[derive(Debug, Clone, Default)]
+struct Triangles {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    /// sRGB colors, empty if the mesh has none
+    colors: Vec<[u8; 4]>,
+    indices: Vec<u32>,
+}
+
+impl Triangles {
+    fn new(chunk: &ChunkMesh) -> Self {
+        let mesh = chunk.mesh;
+        let float3 = |attribute, transform: &dyn Fn(Vec3) -> Vec3| match mesh.attribute(attribute) {
+            Some(VertexAttributeValues::Float32x3(values)) => values
+                .iter()
+                .map(|&value| transform(Vec3::from_array(value)).to_array())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let positions = float3(Mesh::ATTRIBUTE_POSITION, &|position| {
+            chunk.transform.transform_point(position)
+        });
+        let normals = float3(Mesh::ATTRIBUTE_NORMAL, &|normal| {
+            (chunk.transform.rotation * (normal / chunk.transform.scale)).normalize_or_zero()
+        });
+        let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => colors
+                .iter()
+                .map(|&[r, g, b, a]| Color::rgba_linear(r, g, b, a).as_rgba_u8())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let indices = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&idx| idx as u32).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        Self {
+            positions,
+            normals,
+            colors,
+            indices,
+        }
+    }
+
+    /// Append the triangles of `other` with their indices shifted past the own vertices
+    fn extend(&mut self, other: &Triangles) {
+        let offset = self.positions.len() as u32;
+        self.positions.extend(&other.positions);
+        self.normals.extend(&other.normals);
+        self.colors.extend(&other.colors);
+        self.indices
+            .extend(other.indices.iter().map(|idx| idx + offset));
+    }
+
+    fn has_normals(&self) -> bool {
+        self.normals.len() == self.positions.len()
+    }
+
+    fn has_colors(&self) -> bool {
+        self.colors.len() == self.positions.len()
+    }
+}
+
+fn chunk_name(coord: IVec3) -> String {
+    format!("chunk_{}_{}_{}", coord.x, coord.y, coord.z)
+}
+
+/// Write the meshes of the chunks into `path`, usable without a window or an app.
+/// Returns the written files
+pub fn export_terrain(
+    path: impl AsRef<Path>,
+    meshes: &[ChunkMesh],
+    options: ExportOptions,
+) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref().with_extension(options.format.extension());
+    if options.format == ExportFormat::Glb {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        glb::write_glb(&mut writer, meshes, &terrain_standard_material())?;
+        writer.flush()?;
+        return Ok(vec![path]);
+    }
+
+    let chunks: Vec<_> = meshes
+        .iter()
+        .map(|chunk| (chunk_name(chunk.coord), Triangles::new(chunk)))
+        .collect();
+
+    if options.format == ExportFormat::Obj {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        if options.per_chunk {
+            write_obj(&mut writer, &chunks)?;
+        } else {
+            write_obj(&mut writer, &[("terrain".to_string(), merge(&chunks))])?;
+        }
+        writer.flush()?;
+        return Ok(vec![path]);
+    }
+
+    let files = if options.per_chunk {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        chunks
+            .into_iter()
+            .map(|(name, triangles)| {
+                let file_name = format!("{stem}_{name}.{}", options.format.extension());
+                (path.with_file_name(file_name), triangles)
+            })
+            .collect()
+    } else {
+        vec![(path, merge(&chunks))]
+    };
+    for (file, triangles) in &files {
+        let mut writer = BufWriter::new(File::create(file)?);
+        match options.format {
+            ExportFormat::PlyAscii => {
+                write_ply(&mut writer, triangles, false, options.vertex_colors)?
+            }
+            ExportFormat::PlyBinary => {
+                write_ply(&mut writer, triangles, true, options.vertex_colors)?
+            }
+            ExportFormat::Stl => write_stl(&mut writer, triangles)?,
+            ExportFormat::Obj | ExportFormat::Glb => unreachable!(),
+        }
+        writer.flush()?;
+    }
+    Ok(files.into_iter().map(|(file, _)| file).collect())
+}
+
+fn merge(chunks: &[(String, Triangles)]) -> Triangles {
+    let mut merged = Triangles::default();
+    for (_, triangles) in chunks {
+        merged.extend(triangles);
+    }
+    merged
+}
+
+/// OBJ indices are one based and count the vertices or normals of all previous objects,
+/// objects without normals don't add to the normal indices
+fn write_obj(writer: &mut impl Write, objects: &[(String, Triangles)]) -> io::Result<()> {
+    let mut position_offset = 1;
+    let mut normal_offset = 1;
+    for (name, triangles) in objects {
+        writeln!(writer, "o {name}")?;
+        for [x, y, z] in &triangles.positions {
+            writeln!(writer, "v {x} {y} {z}")?;
+        }
+        let has_normals = triangles.has_normals();
+        if has_normals {
+            for [x, y, z] in &triangles.normals {
+                writeln!(writer, "vn {x} {y} {z}")?;
+            }
+        }
+        for triangle in triangles.indices.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]];
+            let [a, b, c] = corners.map(|idx| idx + position_offset);
+            if has_normals {
+                let [na, nb, nc] = corners.map(|idx| idx + normal_offset);
+                writeln!(writer, "f {a}//{na} {b}//{nb} {c}//{nc}")?;
+            } else {
+                writeln!(writer, "f {a} {b} {c}")?;
+            }
+        }
+        position_offset += triangles.positions.len() as u32;
+        if has_normals {
+            normal_offset += triangles.normals.len() as u32;
+        }
+    }
+    Ok(())
+}
+
+fn write_ply(
+    writer: &mut impl Write,
+    triangles: &Triangles,
+    binary: bool,
+    colors: bool,
+) -> io::Result<()> {
+    let normals = triangles.has_normals();
+    let colors = colors && triangles.has_colors();
+    writeln!(writer, "ply")?;
+    if binary {
+        writeln!(writer, "format binary_little_endian 1.0")?;
+    } else {
+        writeln!(writer, "format ascii 1.0")?;
+    }
+    writeln!(writer, "element vertex {}", triangles.positions.len())?;
+    for axis in ["x", "y", "z"] {
+        writeln!(writer, "property float {axis}")?;
+    }
+    if normals {
+        for axis in ["nx", "ny", "nz"] {
+            writeln!(writer, "property float {axis}")?;
+        }
+    }
+    if colors {
+        for channel in ["red", "green", "blue"] {
+            writeln!(writer, "property uchar {channel}")?;
+        }
+    }
+    writeln!(writer, "element face {}", triangles.indices.len() / 3)?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for (i, position) in triangles.positions.iter().enumerate() {
+        let mut floats = position.to_vec();
+        if normals {
+            floats.extend(triangles.normals[i]);
+        }
+        let color: &[u8] = if colors {
+            &triangles.colors[i][..3]
+        } else {
+            &[]
+        };
+        if binary {
+            for value in floats {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(color)?;
+        } else {
+            let values: Vec<String> = floats
+                .iter()
+                .map(|value| value.to_string())
+                .chain(color.iter().map(|channel| channel.to_string()))
+                .collect();
+            writeln!(writer, "{}", values.join(" "))?;
+        }
+    }
+    for triangle in triangles.indices.chunks_exact(3) {
+        if binary {
+            writer.write_all(&[3])?;
+            for idx in triangle {
+                writer.write_all(&idx.to_le_bytes())?;
+            }
+        } else {
+            writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+        }
+    }
+    Ok(())
+}
+
+/// Binary STL, facet normals are computed from the winding
+fn write_stl(writer: &mut impl Write, triangles: &Triangles) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let title = b"terrain-procgen";
+    header[..title.len()].copy_from_slice(title);
+    writer.write_all(&header)?;
+    writer.write_all(&(triangles.indices.len() as u32 / 3).to_le_bytes())?;
+
+    for triangle in triangles.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+            .map(|idx| Vec3::from_array(triangles.positions[idx as usize]));
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        for vector in [normal, a, b, c] {
+            for value in vector.to_array() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        // Attribute byte count
+        writer.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
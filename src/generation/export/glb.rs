@@ -0,0 +1,223 @@
+//! Binary glTF 2.0, every chunk becomes a node with its transform and a mesh in the node's space
+
+use std::io::{self, Write};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use serde_json::{json, Value};
+
+use super::{chunk_name, ChunkMesh};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_TRIANGLES: u32 = 4;
+
+/// JSON and binary parts of the file being built
+#[derive(Default)]
+struct GlbBuilder {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GlbBuilder {
+    /// Append the data as a new buffer view with a single accessor, returns the accessor's index
+    fn push_accessor(
+        &mut self,
+        data: &[u8],
+        count: usize,
+        component_type: u32,
+        kind: &str,
+        target: u32,
+        bounds: Option<(Vec3, Vec3)>,
+    ) -> usize {
+        // Every component is four bytes long, so views stay aligned
+        let offset = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": data.len(),
+            "target": target,
+        }));
+
+        let mut accessor = json!({
+            "bufferView": self.buffer_views.len() - 1,
+            "componentType": component_type,
+            "count": count,
+            "type": kind,
+        });
+        if let Some((min, max)) = bounds {
+            accessor["min"] = json!(min.to_array());
+            accessor["max"] = json!(max.to_array());
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_floats<const N: usize>(
+        &mut self,
+        values: &[[f32; N]],
+        kind: &str,
+        bounds: Option<(Vec3, Vec3)>,
+    ) -> usize {
+        let data: Vec<u8> = values
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let count = values.len();
+        self.push_accessor(
+            &data,
+            count,
+            COMPONENT_FLOAT,
+            kind,
+            TARGET_ARRAY_BUFFER,
+            bounds,
+        )
+    }
+
+    /// Mesh of the chunk, `None` for meshes without triangles since glTF doesn't allow empty ones
+    fn push_mesh(&mut self, name: &str, mesh: &Mesh) -> Option<Value> {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&idx| idx as u32).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if positions.is_empty() || indices.is_empty() {
+            return None;
+        }
+
+        let (min, max) = positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &position| {
+                let position = Vec3::from_array(position);
+                (min.min(position), max.max(position))
+            },
+        );
+        // Readers need the bounds of the positions
+        let position = self.push_floats(positions, "VEC3", Some((min, max)));
+        let mut attributes = json!({ "POSITION": position });
+        if let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        {
+            attributes["NORMAL"] = json!(self.push_floats(normals, "VEC3", None));
+        }
+        // Vertex colors are linear like glTF's
+        if let Some(VertexAttributeValues::Float32x4(colors)) =
+            mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+        {
+            attributes["COLOR_0"] = json!(self.push_floats(colors, "VEC4", None));
+        }
+
+        let data: Vec<u8> = indices.iter().flat_map(|idx| idx.to_le_bytes()).collect();
+        let indices = self.push_accessor(
+            &data,
+            indices.len(),
+            COMPONENT_UNSIGNED_INT,
+            "SCALAR",
+            TARGET_ELEMENT_ARRAY_BUFFER,
+            None,
+        );
+        Some(json!({
+            "name": name,
+            "primitives": [{
+                "attributes": attributes,
+                "indices": indices,
+                "material": 0,
+                "mode": MODE_TRIANGLES,
+            }],
+        }))
+    }
+}
+
+/// glTF's metallic roughness material closest to `material`, reflectance has no equivalent
+fn material_json(material: &StandardMaterial) -> Value {
+    let emissive = material.emissive.as_linear_rgba_f32();
+    json!({
+        "name": "terrain",
+        "pbrMetallicRoughness": {
+            "baseColorFactor": material.base_color.as_linear_rgba_f32(),
+            "metallicFactor": material.metallic,
+            "roughnessFactor": material.perceptual_roughness,
+        },
+        "emissiveFactor": [emissive[0], emissive[1], emissive[2]],
+        "doubleSided": material.double_sided,
+    })
+}
+
+/// Write a scene with a node for every chunk, all of the meshes share `material`
+pub(super) fn write_glb(
+    writer: &mut impl Write,
+    chunks: &[ChunkMesh],
+    material: &StandardMaterial,
+) -> io::Result<()> {
+    let mut builder = GlbBuilder::default();
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+    for chunk in chunks {
+        let name = chunk_name(chunk.coord);
+        let mut node = json!({
+            "name": name,
+            "translation": chunk.transform.translation.to_array(),
+            "rotation": chunk.transform.rotation.to_array(),
+            "scale": chunk.transform.scale.to_array(),
+        });
+        if let Some(mesh) = builder.push_mesh(&name, chunk.mesh) {
+            meshes.push(mesh);
+            node["mesh"] = json!(meshes.len() - 1);
+        }
+        nodes.push(node);
+    }
+
+    let mut document = json!({
+        "asset": { "version": "2.0", "generator": "terrain-procgen" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "materials": [material_json(material)],
+    });
+    if !meshes.is_empty() {
+        document["meshes"] = json!(meshes);
+        document["accessors"] = json!(builder.accessors);
+        document["bufferViews"] = json!(builder.buffer_views);
+        document["buffers"] = json!([{ "byteLength": builder.buffer.len() }]);
+    }
+
+    let mut json = serde_json::to_vec(&document)?;
+    // Chunks are padded to four bytes, JSON with spaces and binary data with zeros
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = builder.buffer;
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let mut length = 12 + 8 + json.len();
+    if !bin.is_empty() {
+        length += 8 + bin.len();
+    }
+    writer.write_all(GLB_MAGIC)?;
+    writer.write_all(&GLB_VERSION.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&CHUNK_JSON.to_le_bytes())?;
+    writer.write_all(&json)?;
+    if !bin.is_empty() {
+        writer.write_all(&(bin.len() as u32).to_le_bytes())?;
+        writer.write_all(&CHUNK_BIN.to_le_bytes())?;
+        writer.write_all(&bin)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,577 @@
+//! Math expressions used as density functions.
+//!
+//! Expressions like `y - 8*fbm(x*0.05, z*0.05) + 2*sin(x)` are parsed into a syntax tree
+//! that is compiled into a tree of closures, constant subexpressions are folded at compile time.
+//! Available variables are `x`, `y`, `z` and `t`, constants are `pi` and `e`.
+
+use std::{fmt, sync::Arc};
+
+use bevy::prelude::*;
+
+use super::{
+    density::DensityFunction,
+    noise::{Fractal, FractalKind, FractalSettings, NoiseFn, NoiseKind},
+};
+
+/// Values of `x`, `y`, `z` and `t`
+type Variables = [f32; 4];
+type Compiled = Box<dyn Fn(&Variables) -> f32 + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionError {
+    pub message: String,
+    /// Byte offset in the source where the error was found
+    pub position: usize,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// Compiled expression that can be used as a density function
+#[derive(Clone)]
+pub struct Expression {
+    source: String,
+    compiled: Arc<Compiled>,
+    /// Value of the `t` variable
+    pub t: f32,
+}
+
+impl Expression {
+    /// Parse and compile the expression, `seed` is used by noise functions
+    pub fn parse(source: &str, seed: u32) -> Result<Self, ExpressionError> {
+        let node = Parser::new(source)?.parse()?;
+        Ok(Self {
+            source: source.to_string(),
+            compiled: Arc::new(node.compile(seed)),
+            t: 0f32,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn evaluate(&self, pos: Vec3) -> f32 {
+        (self.compiled)(&[pos.x, pos.y, pos.z, self.t])
+    }
+}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expression")
+            .field("source", &self.source)
+            .field("t", &self.t)
+            .finish()
+    }
+}
+
+impl DensityFunction for Expression {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.evaluate(pos)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f32),
+    Identifier(usize, usize),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    LeftParen,
+    RightParen,
+    Comma,
+    End,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let token = match c {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'+' => Token::Plus,
+            b'-' => Token::Minus,
+            b'*' => Token::Star,
+            b'/' => Token::Slash,
+            b'%' => Token::Percent,
+            b'^' => Token::Caret,
+            b'(' => Token::LeftParen,
+            b')' => Token::RightParen,
+            b',' => Token::Comma,
+            b'0'..=b'9' | b'.' => {
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                // Exponent
+                if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                    let mut j = i + 1;
+                    if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                        j += 1;
+                    }
+                    if j < bytes.len() && bytes[j].is_ascii_digit() {
+                        i = j;
+                        while i < bytes.len() && bytes[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let number = source[start..i].parse().map_err(|_| ExpressionError {
+                    message: format!("invalid number '{}'", &source[start..i]),
+                    position: start,
+                })?;
+                tokens.push((Token::Number(number), start));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push((Token::Identifier(start, i), start));
+                continue;
+            }
+            _ => {
+                return Err(ExpressionError {
+                    message: format!(
+                        "unexpected character '{}'",
+                        &source[i..].chars().next().unwrap()
+                    ),
+                    position: i,
+                })
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+}
+
+impl BinaryOp {
+    fn apply(self, a: f32, b: f32) -> f32 {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Subtract => a - b,
+            BinaryOp::Multiply => a * b,
+            BinaryOp::Divide => a / b,
+            BinaryOp::Remainder => a.rem_euclid(b),
+            BinaryOp::Power => a.powf(b),
+        }
+    }
+}
+
+/// Functions with a fixed number of arguments
+#[derive(Debug, Clone, Copy)]
+enum MathFunction {
+    Unary(fn(f32) -> f32),
+    Binary(fn(f32, f32) -> f32),
+    Ternary(fn(f32, f32, f32) -> f32),
+}
+
+fn math_function(name: &str) -> Option<MathFunction> {
+    use MathFunction::*;
+
+    Some(match name {
+        "sin" => Unary(f32::sin),
+        "cos" => Unary(f32::cos),
+        "tan" => Unary(f32::tan),
+        "asin" => Unary(f32::asin),
+        "acos" => Unary(f32::acos),
+        "atan" => Unary(f32::atan),
+        "sinh" => Unary(f32::sinh),
+        "cosh" => Unary(f32::cosh),
+        "tanh" => Unary(f32::tanh),
+        "abs" => Unary(f32::abs),
+        "sqrt" => Unary(f32::sqrt),
+        "exp" => Unary(f32::exp),
+        "ln" => Unary(f32::ln),
+        "log2" => Unary(f32::log2),
+        "log10" => Unary(f32::log10),
+        "floor" => Unary(f32::floor),
+        "ceil" => Unary(f32::ceil),
+        "round" => Unary(f32::round),
+        "fract" => Unary(|x| x - x.floor()),
+        "sign" => Unary(f32::signum),
+        "min" => Binary(f32::min),
+        "max" => Binary(f32::max),
+        "pow" => Binary(f32::powf),
+        "atan2" => Binary(f32::atan2),
+        "mod" => Binary(f32::rem_euclid),
+        "step" => Binary(|edge, x| if x < edge { 0f32 } else { 1f32 }),
+        "clamp" => Ternary(|x, min, max| x.max(min).min(max)),
+        "lerp" => Ternary(|a, b, t| a + (b - a) * t),
+        "smoothstep" => Ternary(|edge0, edge1, x| {
+            let t = ((x - edge0) / (edge1 - edge0)).clamp(0f32, 1f32);
+            t * t * (3f32 - 2f32 * t)
+        }),
+        _ => return None,
+    })
+}
+
+fn noise_function(name: &str) -> Option<NoiseKind> {
+    Some(match name {
+        "perlin" => NoiseKind::Perlin,
+        "simplex" => NoiseKind::Simplex,
+        "value" => NoiseKind::Value,
+        "worley" => NoiseKind::Worley,
+        _ => return None,
+    })
+}
+
+/// Fractals use simplex noise with default settings
+fn fractal_function(name: &str) -> Option<FractalKind> {
+    Some(match name {
+        "fbm" => FractalKind::Fbm,
+        "ridged" => FractalKind::Ridged,
+        "billow" => FractalKind::Billow,
+        "turbulence" => FractalKind::Turbulence,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Constant(f32),
+    Variable(usize),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Math(MathFunction, Vec<Node>),
+    Noise(NoiseKind, Vec<Node>),
+    Fractal(FractalKind, Vec<Node>),
+}
+
+impl Node {
+    fn compile(self, seed: u32) -> Compiled {
+        if let Some(value) = self.constant_value() {
+            return Box::new(move |_| value);
+        }
+
+        match self {
+            Node::Constant(value) => Box::new(move |_| value),
+            Node::Variable(i) => Box::new(move |vars| vars[i]),
+            Node::Negate(node) => {
+                let node = node.compile(seed);
+                Box::new(move |vars| -node(vars))
+            }
+            Node::Binary(op, a, b) => {
+                // Specialize the most common operations to avoid dispatching on the operator
+                let (a, b) = (a.compile(seed), b.compile(seed));
+                match op {
+                    BinaryOp::Add => Box::new(move |vars| a(vars) + b(vars)),
+                    BinaryOp::Subtract => Box::new(move |vars| a(vars) - b(vars)),
+                    BinaryOp::Multiply => Box::new(move |vars| a(vars) * b(vars)),
+                    op => Box::new(move |vars| op.apply(a(vars), b(vars))),
+                }
+            }
+            Node::Math(function, args) => {
+                let mut args = args.into_iter().map(|arg| arg.compile(seed));
+                let mut next = || args.next().unwrap();
+                match function {
+                    MathFunction::Unary(f) => {
+                        let a = next();
+                        Box::new(move |vars| f(a(vars)))
+                    }
+                    MathFunction::Binary(f) => {
+                        let (a, b) = (next(), next());
+                        Box::new(move |vars| f(a(vars), b(vars)))
+                    }
+                    MathFunction::Ternary(f) => {
+                        let (a, b, c) = (next(), next(), next());
+                        Box::new(move |vars| f(a(vars), b(vars), c(vars)))
+                    }
+                }
+            }
+            Node::Noise(kind, args) => compile_noise(kind.build(seed), args, seed),
+            Node::Fractal(kind, args) => {
+                let settings = FractalSettings {
+                    kind,
+                    ..Default::default()
+                };
+                compile_noise(
+                    Fractal::new(NoiseKind::Simplex.build(seed), settings),
+                    args,
+                    seed,
+                )
+            }
+        }
+    }
+
+    /// Evaluate the node if it doesn't depend on variables
+    fn constant_value(&self) -> Option<f32> {
+        match self {
+            Node::Constant(value) => Some(*value),
+            Node::Variable(_) | Node::Noise(..) | Node::Fractal(..) => None,
+            Node::Negate(node) => node.constant_value().map(|value| -value),
+            Node::Binary(op, a, b) => Some(op.apply(a.constant_value()?, b.constant_value()?)),
+            Node::Math(function, args) => {
+                let args = args
+                    .iter()
+                    .map(Node::constant_value)
+                    .collect::<Option<Vec<_>>>()?;
+                Some(match function {
+                    MathFunction::Unary(f) => f(args[0]),
+                    MathFunction::Binary(f) => f(args[0], args[1]),
+                    MathFunction::Ternary(f) => f(args[0], args[1], args[2]),
+                })
+            }
+        }
+    }
+}
+
+/// Noise is sampled in as many dimensions as there are arguments
+fn compile_noise(noise: impl NoiseFn + 'static, args: Vec<Node>, seed: u32) -> Compiled {
+    let mut args: Vec<_> = args.into_iter().map(|arg| arg.compile(seed)).collect();
+    match args.len() {
+        2 => {
+            let (y, x) = (args.pop().unwrap(), args.pop().unwrap());
+            Box::new(move |vars| noise.sample_2d(Vec2::new(x(vars), y(vars))))
+        }
+        3 => {
+            let (z, y, x) = (
+                args.pop().unwrap(),
+                args.pop().unwrap(),
+                args.pop().unwrap(),
+            );
+            Box::new(move |vars| noise.sample_3d(Vec3::new(x(vars), y(vars), z(vars))))
+        }
+        _ => {
+            let (w, z, y, x) = (
+                args.pop().unwrap(),
+                args.pop().unwrap(),
+                args.pop().unwrap(),
+                args.pop().unwrap(),
+            );
+            Box::new(move |vars| noise.sample_4d(Vec4::new(x(vars), y(vars), z(vars), w(vars))))
+        }
+    }
+}
+
+/// Recursive descent parser, from lowest to highest precedence:
+/// `+ -`, `* / %`, unary `-`, `^` (right associative), atoms
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(Token, usize)>,
+    current: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Result<Self, ExpressionError> {
+        Ok(Self {
+            source,
+            tokens: tokenize(source)?,
+            current: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Node, ExpressionError> {
+        let node = self.additive()?;
+        match self.peek() {
+            Token::End => Ok(node),
+            _ => Err(self.error("unexpected token")),
+        }
+    }
+
+    fn peek(&self) -> Token {
+        self.tokens[self.current].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.current].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek();
+        if token != Token::End {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> ExpressionError {
+        ExpressionError {
+            message: message.to_string(),
+            position: self.position(),
+        }
+    }
+
+    fn expect(&mut self, token: Token, message: &str) -> Result<(), ExpressionError> {
+        if self.peek() == token {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn additive(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Plus => BinaryOp::Add,
+                Token::Minus => BinaryOp::Subtract,
+                _ => return Ok(node),
+            };
+            self.advance();
+            node = Node::Binary(op, Box::new(node), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Star => BinaryOp::Multiply,
+                Token::Slash => BinaryOp::Divide,
+                Token::Percent => BinaryOp::Remainder,
+                _ => return Ok(node),
+            };
+            self.advance();
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        match self.peek() {
+            Token::Minus => {
+                self.advance();
+                Ok(Node::Negate(Box::new(self.unary()?)))
+            }
+            Token::Plus => {
+                self.advance();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, ExpressionError> {
+        let base = self.atom()?;
+        if self.peek() == Token::Caret {
+            self.advance();
+            // `unary` so that `2^-x` works, recursion makes `^` right associative
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                BinaryOp::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, ExpressionError> {
+        let position = self.position();
+        match self.advance() {
+            Token::Number(value) => Ok(Node::Constant(value)),
+            Token::LeftParen => {
+                let node = self.additive()?;
+                self.expect(Token::RightParen, "expected ')'")?;
+                Ok(node)
+            }
+            Token::Identifier(start, end) => {
+                let name = &self.source[start..end];
+                if self.peek() == Token::LeftParen {
+                    self.advance();
+                    let args = self.arguments()?;
+                    return call(name, args, position);
+                }
+                match name {
+                    "x" => Ok(Node::Variable(0)),
+                    "y" => Ok(Node::Variable(1)),
+                    "z" => Ok(Node::Variable(2)),
+                    "t" => Ok(Node::Variable(3)),
+                    "pi" => Ok(Node::Constant(std::f32::consts::PI)),
+                    "e" => Ok(Node::Constant(std::f32::consts::E)),
+                    _ => Err(ExpressionError {
+                        message: format!("unknown variable '{name}'"),
+                        position,
+                    }),
+                }
+            }
+            Token::End => Err(ExpressionError {
+                message: "unexpected end of expression".to_string(),
+                position,
+            }),
+            _ => Err(ExpressionError {
+                message: "expected a number, variable or function".to_string(),
+                position,
+            }),
+        }
+    }
+
+    /// Comma separated arguments after the opening parenthesis
+    fn arguments(&mut self) -> Result<Vec<Node>, ExpressionError> {
+        let mut args = vec![];
+        if self.peek() == Token::RightParen {
+            self.advance();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.additive()?);
+            match self.peek() {
+                Token::Comma => self.advance(),
+                Token::RightParen => {
+                    self.advance();
+                    return Ok(args);
+                }
+                _ => return Err(self.error("expected ',' or ')'")),
+            };
+        }
+    }
+}
+
+fn call(name: &str, args: Vec<Node>, position: usize) -> Result<Node, ExpressionError> {
+    let arity_error = |expected: &str| ExpressionError {
+        message: format!(
+            "'{name}' takes {expected} arguments but {} were given",
+            args.len()
+        ),
+        position,
+    };
+
+    if let Some(function) = math_function(name) {
+        let arity = match function {
+            MathFunction::Unary(_) => 1,
+            MathFunction::Binary(_) => 2,
+            MathFunction::Ternary(_) => 3,
+        };
+        if args.len() != arity {
+            return Err(arity_error(&arity.to_string()));
+        }
+        return Ok(Node::Math(function, args));
+    }
+
+    match (noise_function(name), fractal_function(name)) {
+        (None, None) => Err(ExpressionError {
+            message: format!("unknown function '{name}'"),
+            position,
+        }),
+        _ if !(2..=4).contains(&args.len()) => Err(arity_error("2 to 4")),
+        (Some(kind), _) => Ok(Node::Noise(kind, args)),
+        (None, Some(kind)) => Ok(Node::Fractal(kind, args)),
+    }
+}
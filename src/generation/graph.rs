@@ -0,0 +1,463 @@
+//! Density functions composed out of primitives and operators
+
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    density::{Capsule, Cuboid, DensityFunction, Plane, Sphere, TerrainDensity, Torus},
+    material::MaterialId,
+    noise::{NoiseDimensions, NoiseSettings},
+};
+
+/// Tree of density nodes that can be saved alongside the config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DensityGraph {
+    pub root: DensityNode,
+}
+
+impl DensityGraph {
+    pub fn build(&self, seed: u32) -> Result<TerrainDensity, GraphError> {
+        Ok(TerrainDensity(self.root.compile(seed)?.into()))
+    }
+}
+
+/// Node parameters that don't describe a density function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphError {
+    /// Scale factor that isn't positive, zero collapses the node
+    /// and negative ones turn it inside out
+    InvalidScale(f32),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::InvalidScale(factor) => {
+                write!(f, "scale factor {factor} isn't positive")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl Default for DensityGraph {
+    /// Rolling hills with a floating island above them and a tunnel through them
+    fn default() -> Self {
+        use DensityNode::*;
+
+        let mut hills = NoiseSettings {
+            frequency: 0.08f32,
+            amplitude: 2f32,
+            ground_level: 4f32,
+            ..Default::default()
+        };
+        hills.fractal.octaves = 3;
+
+        Self {
+            root: Difference {
+                base: Box::new(Union {
+                    nodes: vec![
+                        Heightfield { noise: hills },
+                        Translate {
+                            offset: Vec3::new(8f32, 11f32, 8f32),
+                            node: Box::new(SmoothUnion {
+                                radius: 1.5f32,
+                                nodes: vec![
+                                    Sphere {
+                                        center: Vec3::ZERO,
+                                        radius: 2.5f32,
+                                    },
+                                    Cuboid {
+                                        center: Vec3::new(0f32, 1f32, 0f32),
+                                        half_extents: Vec3::new(3.5f32, 0.5f32, 3.5f32),
+                                    },
+                                ],
+                            }),
+                        },
+                    ],
+                }),
+                subtract: Box::new(Capsule {
+                    start: Vec3::new(-1f32, 3f32, 4f32),
+                    end: Vec3::new(17f32, 3f32, 12f32),
+                    radius: 1.5f32,
+                }),
+            },
+        }
+    }
+}
+
+/// Node of a [`DensityGraph`], primitives are leaves,
+/// operators combine or transform their children
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DensityNode {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    Cuboid {
+        center: Vec3,
+        half_extents: Vec3,
+    },
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: f32,
+    },
+    /// Ring lying in the XZ plane
+    Torus {
+        center: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Plane {
+        normal: Vec3,
+        offset: f32,
+    },
+    /// Ground displaced by 2D noise, the dimensions of the noise settings are ignored
+    Heightfield {
+        noise: NoiseSettings,
+    },
+    /// Solid where any of the nodes is solid
+    Union {
+        nodes: Vec<DensityNode>,
+    },
+    /// Solid where all of the nodes are solid
+    Intersection {
+        nodes: Vec<DensityNode>,
+    },
+    /// Carve `subtract` out of `base`
+    Difference {
+        base: Box<DensityNode>,
+        subtract: Box<DensityNode>,
+    },
+    /// Union that blends nodes together within `radius` of each other,
+    /// the order of the nodes doesn't matter
+    SmoothUnion {
+        radius: f32,
+        nodes: Vec<DensityNode>,
+    },
+    Translate {
+        offset: Vec3,
+        node: Box<DensityNode>,
+    },
+    Rotate {
+        rotation: Quat,
+        node: Box<DensityNode>,
+    },
+    /// Uniform scale, non-uniform scale would distort distances
+    Scale {
+        factor: f32,
+        node: Box<DensityNode>,
+    },
+    /// Repeat the node infinitely with the given period,
+    /// axes with zero period are not repeated
+    Repeat {
+        period: Vec3,
+        node: Box<DensityNode>,
+    },
+    /// Give the node a material, materials set deeper in the node take precedence
+    Material {
+        material: MaterialId,
+        node: Box<DensityNode>,
+    },
+}
+
+impl DensityNode {
+    /// Turn the node into a density function, `seed` is used by noise primitives
+    pub fn compile(&self, seed: u32) -> Result<Box<dyn DensityFunction>, GraphError> {
+        let compile_all = |nodes: &[DensityNode]| -> Result<Vec<Box<dyn DensityFunction>>, _> {
+            nodes.iter().map(|node| node.compile(seed)).collect()
+        };
+
+        Ok(match self {
+            DensityNode::Sphere { center, radius } => Box::new(Sphere {
+                center: *center,
+                radius: *radius,
+            }),
+            DensityNode::Cuboid {
+                center,
+                half_extents,
+            } => Box::new(Cuboid {
+                center: *center,
+                half_extents: *half_extents,
+            }),
+            DensityNode::Capsule { start, end, radius } => Box::new(Capsule {
+                start: *start,
+                end: *end,
+                radius: *radius,
+            }),
+            DensityNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => Box::new(Torus {
+                center: *center,
+                major_radius: *major_radius,
+                minor_radius: *minor_radius,
+            }),
+            DensityNode::Plane { normal, offset } => Box::new(Plane {
+                normal: *normal,
+                offset: *offset,
+            }),
+            DensityNode::Heightfield { noise } => {
+                let settings = NoiseSettings {
+                    dimensions: NoiseDimensions::Surface,
+                    ..*noise
+                };
+                Box::new(settings.build(seed))
+            }
+            DensityNode::Union { nodes } => Box::new(Union(compile_all(nodes)?)),
+            DensityNode::Intersection { nodes } => Box::new(Intersection(compile_all(nodes)?)),
+            DensityNode::Difference { base, subtract } => Box::new(Difference {
+                base: base.compile(seed)?,
+                subtract: subtract.compile(seed)?,
+            }),
+            DensityNode::SmoothUnion { radius, nodes } => Box::new(SmoothUnion {
+                radius: *radius,
+                nodes: compile_all(nodes)?,
+            }),
+            DensityNode::Translate { offset, node } => Box::new(Translate {
+                offset: *offset,
+                node: node.compile(seed)?,
+            }),
+            DensityNode::Rotate { rotation, node } => Box::new(Rotate {
+                inverse_rotation: rotation.normalize().inverse(),
+                node: node.compile(seed)?,
+            }),
+            DensityNode::Scale { factor, .. } if *factor <= 0f32 || factor.is_nan() => {
+                return Err(GraphError::InvalidScale(*factor));
+            }
+            DensityNode::Scale { factor, node } => Box::new(Scale {
+                factor: *factor,
+                node: node.compile(seed)?,
+            }),
+            DensityNode::Repeat { period, node } => Box::new(Repeat {
+                period: *period,
+                node: node.compile(seed)?,
+            }),
+            DensityNode::Material { material, node } => Box::new(Material {
+                material: *material,
+                node: node.compile(seed)?,
+            }),
+        })
+    }
+}
+
+/// Value and material of the node with the smallest or the largest value
+fn select_material(
+    nodes: &[Box<dyn DensityFunction>],
+    pos: Vec3,
+    smallest: bool,
+) -> (f32, Option<MaterialId>) {
+    nodes
+        .iter()
+        .map(|node| node.sample_material(pos))
+        .reduce(|a, b| if (b.0 < a.0) == smallest { b } else { a })
+        .unwrap_or((if smallest { f32::MAX } else { f32::MIN }, None))
+}
+
+struct Union(Vec<Box<dyn DensityFunction>>);
+
+impl DensityFunction for Union {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.0
+            .iter()
+            .map(|node| node.sample(pos))
+            .fold(f32::MAX, f32::min)
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        select_material(&self.0, pos, true)
+    }
+}
+
+struct Intersection(Vec<Box<dyn DensityFunction>>);
+
+impl DensityFunction for Intersection {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.0
+            .iter()
+            .map(|node| node.sample(pos))
+            .fold(f32::MIN, f32::max)
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        select_material(&self.0, pos, false)
+    }
+}
+
+struct Difference {
+    base: Box<dyn DensityFunction>,
+    subtract: Box<dyn DensityFunction>,
+}
+
+impl DensityFunction for Difference {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.base.sample(pos).max(-self.subtract.sample(pos))
+    }
+
+    /// Carved walls keep the material of the base
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        let (value, material) = self.base.sample_material(pos);
+        (value.max(-self.subtract.sample(pos)), material)
+    }
+}
+
+struct SmoothUnion {
+    radius: f32,
+    nodes: Vec<Box<dyn DensityFunction>>,
+}
+
+impl SmoothUnion {
+    /// Exponential smooth minimum, accumulated as the smallest value so far and the sum
+    /// of the weights of all values relative to it. Unlike folding a smooth minimum of two values
+    /// pairwise the result doesn't depend on the order of the nodes. Its sharpness is a quarter
+    /// of the radius, values further apart than the radius barely blend
+    fn blend(&self, (min, weights): (f32, f32), value: f32) -> (f32, f32) {
+        let sharpness = self.radius.max(f32::EPSILON) / 4f32;
+        if value < min {
+            (value, weights * ((value - min) / sharpness).exp() + 1f32)
+        } else if value == min {
+            (min, weights + 1f32)
+        } else {
+            (min, weights + ((min - value) / sharpness).exp())
+        }
+    }
+
+    fn blended(&self, (min, weights): (f32, f32)) -> f32 {
+        if weights == 0f32 {
+            return f32::MAX;
+        }
+        min - self.radius.max(f32::EPSILON) / 4f32 * weights.ln()
+    }
+}
+
+impl DensityFunction for SmoothUnion {
+    fn sample(&self, pos: Vec3) -> f32 {
+        let blend = self
+            .nodes
+            .iter()
+            .map(|node| node.sample(pos))
+            .fold((f32::INFINITY, 0f32), |blend, value| {
+                self.blend(blend, value)
+            });
+        self.blended(blend)
+    }
+
+    /// Blended areas take the material of the closest node
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        let (blend, _, material) = self
+            .nodes
+            .iter()
+            .map(|node| node.sample_material(pos))
+            .fold(
+                ((f32::INFINITY, 0f32), f32::INFINITY, None),
+                |(blend, closest, material), (value, value_material)| {
+                    let material = if value < closest {
+                        value_material
+                    } else {
+                        material
+                    };
+                    (self.blend(blend, value), closest.min(value), material)
+                },
+            );
+        (self.blended(blend), material)
+    }
+}
+
+struct Translate {
+    offset: Vec3,
+    node: Box<dyn DensityFunction>,
+}
+
+impl DensityFunction for Translate {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.node.sample(pos - self.offset)
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        self.node.sample_material(pos - self.offset)
+    }
+}
+
+struct Rotate {
+    inverse_rotation: Quat,
+    node: Box<dyn DensityFunction>,
+}
+
+impl DensityFunction for Rotate {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.node.sample(self.inverse_rotation * pos)
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        self.node.sample_material(self.inverse_rotation * pos)
+    }
+}
+
+struct Scale {
+    factor: f32,
+    node: Box<dyn DensityFunction>,
+}
+
+impl DensityFunction for Scale {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.node.sample(pos / self.factor) * self.factor
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        let (value, material) = self.node.sample_material(pos / self.factor);
+        (value * self.factor, material)
+    }
+}
+
+struct Repeat {
+    period: Vec3,
+    node: Box<dyn DensityFunction>,
+}
+
+impl Repeat {
+    fn repeat(&self, pos: Vec3) -> Vec3 {
+        let repeat = |p: f32, period: f32| {
+            if period > 0f32 {
+                p - period * (p / period).round()
+            } else {
+                p
+            }
+        };
+        Vec3::new(
+            repeat(pos.x, self.period.x),
+            repeat(pos.y, self.period.y),
+            repeat(pos.z, self.period.z),
+        )
+    }
+}
+
+impl DensityFunction for Repeat {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.node.sample(self.repeat(pos))
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        self.node.sample_material(self.repeat(pos))
+    }
+}
+
+struct Material {
+    material: MaterialId,
+    node: Box<dyn DensityFunction>,
+}
+
+impl DensityFunction for Material {
+    fn sample(&self, pos: Vec3) -> f32 {
+        self.node.sample(pos)
+    }
+
+    fn sample_material(&self, pos: Vec3) -> (f32, Option<MaterialId>) {
+        let (value, material) = self.node.sample_material(pos);
+        (value, material.or(Some(self.material)))
+    }
+}
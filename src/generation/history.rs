@@ -0,0 +1,178 @@
+//! Undoing and redoing terrain edits, edits are stored as the changed point values of each chunk
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::{TerrainChunk, TerrainEdit};
+
+/// Undo or redo the latest brush stroke
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCommand {
+    Undo,
+    Redo,
+}
+
+/// Value before and after the step by point index of a single chunk
+type ChunkDiff = HashMap<u32, (f32, f32)>;
+
+/// Changes of one brush stroke, an undo step
+#[derive(Debug, Clone, Default)]
+pub struct EditStep {
+    /// Diffs by chunk coordinates and the level of detail their indices belong to,
+    /// points a chunk keeps for another level of detail have their own diff
+    chunks: HashMap<(IVec3, u32), ChunkDiff>,
+}
+
+impl EditStep {
+    /// Coordinates of the chunks changed by the step
+    pub fn coords(&self) -> impl Iterator<Item = IVec3> + '_ {
+        let coords: HashSet<_> = self.chunks.keys().map(|&(coord, _)| coord).collect();
+        coords.into_iter()
+    }
+
+    /// Amount of changed points over all chunks
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Restore the values the chunk's points had before the step,
+    /// returns whether the step changed the chunk
+    pub fn revert(&self, chunk: &mut TerrainChunk) -> bool {
+        self.write(chunk, |(before, _)| before)
+    }
+
+    /// Set the values the chunk's points had after the step,
+    /// returns whether the step changed the chunk
+    pub fn reapply(&self, chunk: &mut TerrainChunk) -> bool {
+        self.write(chunk, |(_, after)| after)
+    }
+
+    fn write(&self, chunk: &mut TerrainChunk, value: impl Fn((f32, f32)) -> f32 + Copy) -> bool {
+        if let Some(stored) = &mut chunk.stored {
+            self.write(stored, value);
+        }
+        // Indices of another level of detail point elsewhere
+        let Some(diff) = self.chunks.get(&(chunk.coord, chunk.lod)) else {
+            return false;
+        };
+        for (&idx, &change) in diff {
+            chunk.points[idx as usize].value = value(change);
+        }
+        !diff.is_empty()
+    }
+}
+
+/// Undo and redo stacks of the edits,
+/// consecutive edits are collected into one step until the stroke ends
+#[derive(Resource, Debug, Default)]
+pub struct EditHistory {
+    undo: VecDeque<EditStep>,
+    redo: Vec<EditStep>,
+    /// Step of the stroke still in progress
+    stroke: Option<EditStep>,
+}
+
+impl EditHistory {
+    /// Apply the brush to the chunk and record the changes into the current stroke,
+    /// returns whether any point changed
+    pub fn apply_edit(&mut self, chunk: &mut TerrainChunk, edit: &TerrainEdit) -> bool {
+        // Points kept for another level of detail have to stay up to date
+        if let Some(stored) = &mut chunk.stored {
+            self.apply_edit(stored, edit);
+        }
+
+        self.record(chunk, |chunk, on_change| chunk.edit_points(edit, on_change))
+    }
+
+    /// Copy the points shared with a neighbouring chunk and record the changes
+    /// into the current stroke, returns whether any point changed
+    pub fn copy_shared_points(
+        &mut self,
+        chunk: &mut TerrainChunk,
+        neighbour: &TerrainChunk,
+    ) -> bool {
+        self.record(chunk, |chunk, on_change| {
+            chunk.copy_points(neighbour, on_change)
+        })
+    }
+
+    fn record(
+        &mut self,
+        chunk: &mut TerrainChunk,
+        change: impl FnOnce(&mut TerrainChunk, &mut dyn FnMut(u32, f32, f32)) -> bool,
+    ) -> bool {
+        let key = (chunk.coord, chunk.lod);
+        let step = self.stroke.get_or_insert_with(EditStep::default);
+        let diff = step.chunks.entry(key).or_default();
+        let touched = change(chunk, &mut |idx, before, after| {
+            // Only the first value before the stroke matters
+            diff.entry(idx)
+                .and_modify(|change| change.1 = after)
+                .or_insert((before, after));
+        });
+        if !touched && diff.is_empty() {
+            step.chunks.remove(&key);
+        }
+        touched
+    }
+
+    /// Close the current stroke, keeping at most `size` undo steps
+    pub fn end_stroke(&mut self, size: usize) {
+        if let Some(step) = self.stroke.take().filter(|step| !step.is_empty()) {
+            // A new edit makes the undone steps unreachable
+            self.redo.clear();
+            self.undo.push_back(step);
+        }
+        while self.undo.len() > size {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Step to revert on the chunks, it is moved onto the redo stack
+    pub fn undo(&mut self, size: usize) -> Option<&EditStep> {
+        self.end_stroke(size);
+        let step = self.undo.pop_back()?;
+        self.redo.push(step);
+        self.redo.last()
+    }
+
+    /// Step to reapply on the chunks, it is moved back onto the undo stack
+    pub fn redo(&mut self, size: usize) -> Option<&EditStep> {
+        self.end_stroke(size);
+        let step = self.redo.pop()?;
+        self.undo.push_back(step);
+        self.undo.back()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.stroke.as_ref().is_some_and(|step| !step.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Amount of changed points kept over all steps
+    pub fn stored_points(&self) -> usize {
+        self.undo
+            .iter()
+            .chain(&self.redo)
+            .chain(&self.stroke)
+            .map(EditStep::len)
+            .sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.stroke = None;
+    }
+}
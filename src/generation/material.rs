@@ -0,0 +1,88 @@
+//! Materials of the terrain, every point of a chunk has one and meshes carry them in vertex attributes
+
+use bevy::{
+    prelude::*,
+    render::{mesh::MeshVertexAttribute, render_resource::VertexFormat},
+};
+use serde::{Deserialize, Serialize};
+
+/// Material of a single point of the terrain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum MaterialId {
+    #[default]
+    Rock,
+    Dirt,
+    Grass,
+    Sand,
+    Snow,
+}
+
+impl MaterialId {
+    pub const ALL: [MaterialId; 5] = [
+        MaterialId::Rock,
+        MaterialId::Dirt,
+        MaterialId::Grass,
+        MaterialId::Sand,
+        MaterialId::Snow,
+    ];
+
+    /// Base color the material is drawn with
+    pub fn color(self) -> Color {
+        match self {
+            MaterialId::Rock => Color::rgb(0.45, 0.43, 0.4),
+            MaterialId::Dirt => Color::rgb(0.4, 0.3, 0.2),
+            MaterialId::Grass => Color::rgb(0.3, 0.5, 0.3),
+            MaterialId::Sand => Color::rgb(0.76, 0.7, 0.5),
+            MaterialId::Snow => Color::rgb(0.95, 0.95, 0.97),
+        }
+    }
+}
+
+/// Index of the [`MaterialId`] of every vertex, vertices of a triangle may have different
+/// materials, the vertex colors blend them across the triangle
+pub const ATTRIBUTE_MATERIAL_ID: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_MaterialId", 1_170_468_211, VertexFormat::Uint32);
+
+/// Picks materials for the points the density function leaves to the rules
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialRules {
+    /// Surfaces steeper than this angle in degrees are bare rock
+    pub cliff_angle: f32,
+    /// Everything else above this height is covered in snow
+    pub snow_height: f32,
+    /// Everything else below this height is sand
+    pub sand_height: f32,
+    /// Grass grows down to this depth under the surface, dirt is below it
+    pub soil_depth: f32,
+}
+
+impl Default for MaterialRules {
+    fn default() -> Self {
+        Self {
+            cliff_angle: 45f32,
+            snow_height: 12f32,
+            sand_height: -2f32,
+            soil_depth: 1f32,
+        }
+    }
+}
+
+impl MaterialRules {
+    /// Material of a point at `position`, `normal` points out of the terrain
+    /// and `depth` is how far under the surface the point is
+    pub fn material(&self, position: Vec3, normal: Vec3, depth: f32) -> MaterialId {
+        if normal.y < self.cliff_angle.to_radians().cos() {
+            MaterialId::Rock
+        } else if position.y > self.snow_height {
+            MaterialId::Snow
+        } else if position.y < self.sand_height {
+            MaterialId::Sand
+        } else if depth > self.soil_depth {
+            MaterialId::Dirt
+        } else {
+            MaterialId::Grass
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Extraction of the isosurface out of sampled chunks
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+mod dual_contouring;
+mod marching_cubes;
+mod marching_cubes_33;
+mod marching_tetrahedra;
+mod surface_nets;
+
+pub use dual_contouring::DualContouring;
+pub use marching_cubes::MarchingCubes;
+pub use marching_cubes_33::MarchingCubes33;
+pub use marching_tetrahedra::MarchingTetrahedra;
+pub use surface_nets::SurfaceNets;
+
+use super::{
+    coloring::ColorMode,
+    material::ATTRIBUTE_MATERIAL_ID,
+    tables::*,
+    triplanar::{ATTRIBUTE_SPLAT_WEIGHTS, ATTRIBUTE_TRIPLANAR_WEIGHTS},
+    utils::*,
+    DensityFunction, MaterialId, MeshIndexFormat, Point, TerrainChunk, TerrainGeneratorConfig,
+    CHUNK_PADDING,
+};
+
+/// Triangles extracted out of a chunk
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    /// Material of every vertex
+    pub materials: Vec<MaterialId>,
+    pub indices: Vec<u32>,
+    /// Triangles of the cubes right around the chunk, they only contribute to normals
+    /// so that vertices on the chunk's border get the same normals as in the neighbours
+    pub padding_indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Add a vertex and return its index
+    pub fn push_vertex(&mut self, position: Vec3, material: MaterialId) -> u32 {
+        self.positions.push(position);
+        self.materials.push(material);
+        (self.positions.len() - 1) as u32
+    }
+
+    /// Triangle list for the cube, cubes are counted from the chunk's position
+    pub fn triangles(&mut self, chunk: &TerrainChunk, cube: IVec3) -> &mut Vec<u32> {
+        if cube.cmpge(IVec3::ZERO).all() && cube.cmplt(chunk.size.as_ivec3()).all() {
+            &mut self.indices
+        } else {
+            &mut self.padding_indices
+        }
+    }
+}
+
+pub trait Mesher {
+    /// Triangulate the surface where the chunk's points cross `isolevel`
+    /// in the chunk and the layer of cubes around it,
+    /// `density` is the function the points were sampled from, if they weren't modified since
+    fn mesh(
+        &self,
+        chunk: &TerrainChunk,
+        isolevel: f32,
+        density: Option<&dyn DensityFunction>,
+    ) -> MeshData;
+}
+
+/// Surface extraction algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MesherKind {
+    #[default]
+    MarchingCubes,
+    /// Partial Marching Cubes 33, tunnels branching out into three contours aren't built
+    MarchingCubes33,
+    MarchingTetrahedra,
+    SurfaceNets,
+    DualContouring,
+}
+
+impl MesherKind {
+    pub fn mesher(self) -> Box<dyn Mesher> {
+        match self {
+            MesherKind::MarchingCubes => Box::new(MarchingCubes),
+            MesherKind::MarchingCubes33 => Box::new(MarchingCubes33),
+            MesherKind::MarchingTetrahedra => Box::new(MarchingTetrahedra),
+            MesherKind::SurfaceNets => Box::new(SurfaceNets),
+            MesherKind::DualContouring => Box::new(DualContouring::default()),
+        }
+    }
+}
+
+/// How vertex normals are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NormalMode {
+    /// Sum of the normals of the triangles around the vertex weighted by their area
+    #[default]
+    FaceWeighted,
+    /// Gradient of the density function, continuous across chunks
+    Gradient,
+}
+
+/// Build a mesh out of the sampled chunk with the configured mesher
+pub fn mesh_chunk(
+    chunk: &TerrainChunk,
+    config: &TerrainGeneratorConfig,
+    density: Option<&dyn DensityFunction>,
+) -> Mesh {
+    let MeshData {
+        positions: vertices,
+        materials,
+        indices,
+        padding_indices,
+    } = config.mesher.mesher().mesh(chunk, config.isolevel, density);
+
+    let normals = match config.normals {
+        NormalMode::FaceWeighted => {
+            face_weighted_normals(&vertices, indices.iter().chain(&padding_indices))
+        }
+        NormalMode::Gradient => vertices
+            .iter()
+            .map(|&vertex| match density {
+                Some(density) => {
+                    density_gradient(density, vertex, chunk.cube_size.min_element() * 0.01f32)
+                }
+                None => grid_gradient(chunk, vertex),
+            })
+            .collect(),
+    };
+    let colors = match config.coloring.mode {
+        ColorMode::Materials => materials
+            .iter()
+            .map(|material| material.color().as_linear_rgba_f32())
+            .collect(),
+        // The padding triangles keep the curvature continuous across chunks
+        ColorMode::Ramps => config.coloring.vertex_colors(
+            &vertices,
+            &normals,
+            &[&indices[..], &padding_indices].concat(),
+        ),
+    };
+
+    let mut indices = indices;
+    let used_vertices = remove_unused_vertices(&mut indices, vertices.len());
+    let mut vertices = gather(&vertices, &used_vertices);
+    let mut normals = gather(&normals, &used_vertices);
+    let mut materials = gather(&materials, &used_vertices);
+    let mut colors = gather(&colors, &used_vertices);
+
+    // Neighbours with a different level of detail don't meet the chunk exactly
+    if config.lod_levels > 0 {
+        let skirt_sources = add_skirts(chunk, &mut vertices, &mut normals, &mut indices);
+        materials.extend(gather(&materials, &skirt_sources));
+        colors.extend(gather(&colors, &skirt_sources));
+    }
+
+    // 16 bit indices can only address 65536 vertices
+    let indices = match config.index_format {
+        MeshIndexFormat::Auto if vertices.len() <= u16::MAX as usize + 1 => {
+            Indices::U16(indices.into_iter().map(|idx| idx as u16).collect())
+        }
+        _ => Indices::U32(indices),
+    };
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    if config.splatting.enabled {
+        let splatting = &config.splatting;
+        mesh.insert_attribute(
+            ATTRIBUTE_TRIPLANAR_WEIGHTS,
+            normals
+                .iter()
+                .map(|&normal| splatting.triplanar_weights(normal))
+                .collect::<Vec<_>>(),
+        );
+        mesh.insert_attribute(
+            ATTRIBUTE_SPLAT_WEIGHTS,
+            vertices
+                .iter()
+                .zip(&normals)
+                .zip(&materials)
+                .map(|((&vertex, &normal), &material)| {
+                    splatting.splat_weights(vertex, normal, material)
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    // Colors are interpolated across triangles, blending the colors of their vertices
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(
+        ATTRIBUTE_MATERIAL_ID,
+        materials
+            .iter()
+            .map(|&material| material as u32)
+            .collect::<Vec<_>>(),
+    );
+    mesh.set_indices(Some(indices));
+    mesh
+}
+
+fn face_weighted_normals<'a>(
+    vertices: &[Vec3],
+    indices: impl Iterator<Item = &'a u32>,
+) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    let indices: Vec<usize> = indices.map(|&idx| idx as usize).collect();
+    for chunk in indices.chunks_exact(3) {
+        let idx_a = chunk[0];
+        let idx_b = chunk[1];
+        let idx_c = chunk[2];
+
+        let vertex_a = vertices[idx_a];
+        let vertex_b = vertices[idx_b];
+        let vertex_c = vertices[idx_c];
+
+        let edge_ab = vertex_b - vertex_a;
+        let edge_ac = vertex_c - vertex_a;
+
+        let wheighted_normal = edge_ab.cross(edge_ac);
+
+        normals[idx_a] += wheighted_normal;
+        normals[idx_b] += wheighted_normal;
+        normals[idx_c] += wheighted_normal;
+    }
+
+    for n in normals.iter_mut() {
+        *n = n.normalize();
+    }
+    normals
+}
+
+/// Drop vertices that are only used by the padding triangles, `indices` are updated
+/// to the new vertex order and the original indices of the kept vertices are returned
+fn remove_unused_vertices(indices: &mut [u32], vertex_count: usize) -> Vec<u32> {
+    let mut new_indices = vec![u32::MAX; vertex_count];
+    let mut used_vertices = Vec::with_capacity(vertex_count);
+    for idx in indices.iter_mut() {
+        let new_idx = &mut new_indices[*idx as usize];
+        if *new_idx == u32::MAX {
+            *new_idx = used_vertices.len() as u32;
+            used_vertices.push(*idx);
+        }
+        *idx = *new_idx;
+    }
+    used_vertices
+}
+
+/// Values of the vertices with the given indices
+fn gather<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|&idx| values[idx as usize]).collect()
+}
+
+/// Normalized gradient of the density function
+fn density_gradient(density: &dyn DensityFunction, pos: Vec3, step: f32) -> Vec3 {
+    density.gradient(pos, step).normalize_or_zero()
+}
+
+/// Normalized gradient of the sampled points, central differences at the points
+/// around `pos` are interpolated trilinearly, one sided differences are used at the padding
+fn grid_gradient(chunk: &TerrainChunk, pos: Vec3) -> Vec3 {
+    let min_idx = IVec3::splat(-(CHUNK_PADDING as i32));
+    let max_idx = chunk.size.as_ivec3() + CHUNK_PADDING as i32;
+    let local = (pos - chunk.position) / chunk.cube_size;
+    let cube = local.floor().as_ivec3().clamp(min_idx, max_idx - 1);
+    let t = (local - cube.as_vec3()).clamp(Vec3::ZERO, Vec3::ONE);
+    CUBE_CORNERS
+        .iter()
+        .map(|offset| {
+            let weight = Vec3::select(offset.cmpeq(UVec3::ONE), t, Vec3::ONE - t);
+            chunk.point_gradient(cube + offset.as_ivec3()) * weight.x * weight.y * weight.z
+        })
+        .sum::<Vec3>()
+        .normalize_or_zero()
+}
+
+/// Mesh with a single vertex in every cube crossing the surface, placed by `place_vertex`
+/// out of the cube's corners and the points where the surface crosses its edges.
+/// Every edge crossing the surface connects the vertices of the four cubes around it
+fn dual_mesh(
+    chunk: &TerrainChunk,
+    isolevel: f32,
+    mut place_vertex: impl FnMut(&[Point; 8], &[Vec3]) -> Vec3,
+) -> MeshData {
+    let mut mesh = MeshData::default();
+
+    // Vertices of the cubes from two before the chunk up to the one after it,
+    // the outer ones are only used by the padding triangles
+    let cubes = chunk.size + UVec3::splat(3);
+    let cube_index = |cube: IVec3| from_3D_to_1D_index((cube + 2).as_uvec3(), cubes) as usize;
+    let mut cube_vertices = vec![u32::MAX; (cubes.x * cubes.y * cubes.z) as usize];
+    let mut crossings = Vec::with_capacity(EDGE_VERTICES.len());
+    for z in -2..=chunk.size.z as i32 {
+        for y in -2..=chunk.size.y as i32 {
+            for x in -2..=chunk.size.x as i32 {
+                let origin = IVec3::new(x, y, z);
+                let cube = CUBE_CORNERS.map(|offset| chunk.point(origin + offset.as_ivec3()));
+
+                crossings.clear();
+                for (p1_idx, p2_idx) in EDGE_VERTICES {
+                    let (p1, p2) = (cube[p1_idx as usize], cube[p2_idx as usize]);
+                    if (p1.value < isolevel) != (p2.value < isolevel) {
+                        crossings.push(vertex_lerp(isolevel, p1, p2));
+                    }
+                }
+                if crossings.is_empty() {
+                    continue;
+                }
+
+                let vertex = place_vertex(&cube, &crossings);
+                cube_vertices[cube_index(origin)] =
+                    mesh.push_vertex(vertex, cube_material(&cube, isolevel));
+            }
+        }
+    }
+
+    // Edges starting on the far faces of the chunk belong to the neighbours,
+    // the ones around the chunk make the padding triangles
+    for z in -1..=chunk.size.z as i32 {
+        for y in -1..=chunk.size.y as i32 {
+            for x in -1..=chunk.size.x as i32 {
+                let start = IVec3::new(x, y, z);
+                let start_solid = chunk.point(start).value < isolevel;
+                for axis in 0..3 {
+                    let [edge, side_a, side_b] =
+                        [axis, (axis + 1) % 3, (axis + 2) % 3].map(|axis| {
+                            let mut unit = IVec3::ZERO;
+                            unit[axis] = 1;
+                            unit
+                        });
+                    if start_solid == (chunk.point(start + edge).value < isolevel) {
+                        continue;
+                    }
+
+                    // Cubes sharing an edge that crosses the surface always have a vertex
+                    let mut quad = [
+                        start,
+                        start - side_a,
+                        start - side_a - side_b,
+                        start - side_b,
+                    ]
+                    .map(|cube| cube_vertices[cube_index(cube)]);
+                    // Counter clockwise when looking from the empty side
+                    if !start_solid {
+                        quad.reverse();
+                    }
+
+                    // Split along the shorter diagonal for better shaped triangles
+                    let diagonal = |a: usize, b: usize| {
+                        mesh.positions[quad[a] as usize]
+                            .distance_squared(mesh.positions[quad[b] as usize])
+                    };
+                    let [a, b, c, d] = quad;
+                    let triangles = if diagonal(0, 2) <= diagonal(1, 3) {
+                        [a, b, c, a, c, d]
+                    } else {
+                        [b, c, d, b, d, a]
+                    };
+                    mesh.triangles(chunk, start).extend(triangles);
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Most common material of the solid corners of a cube
+fn cube_material(cube: &[Point; 8], isolevel: f32) -> MaterialId {
+    let mut counts = [0; MaterialId::ALL.len()];
+    for point in cube.iter().filter(|point| point.value < isolevel) {
+        counts[point.material as usize] += 1;
+    }
+    // Ties go to the first material in the order of `MaterialId::ALL`
+    MaterialId::ALL
+        .into_iter()
+        .rev()
+        .max_by_key(|&material| counts[material as usize])
+        .unwrap_or_default()
+}
+
+/// Hang a strip of triangles from the open border of the mesh into the terrain,
+/// it covers cracks between chunks with different levels of detail.
+/// Returns the border vertex every added vertex hangs from
+fn add_skirts(
+    chunk: &TerrainChunk,
+    vertices: &mut Vec<Vec3>,
+    normals: &mut Vec<Vec3>,
+    indices: &mut Vec<u32>,
+) -> Vec<u32> {
+    // Deep enough to reach under the surface of a neighbour twice as coarse
+    let depth = 2f32 * chunk.cube_size.max_element();
+
+    // Border edges belong to a single triangle
+    let mut edge_uses = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let (a, b) = (triangle[a], triangle[b]);
+            edge_uses.entry((a.min(b), a.max(b))).or_insert((a, b, 0)).2 += 1;
+        }
+    }
+
+    let mut skirt_vertices = HashMap::new();
+    let mut sources = vec![];
+    let mut skirt_vertex = |idx: u32| {
+        *skirt_vertices.entry(idx).or_insert_with(|| {
+            let normal = normals[idx as usize];
+            vertices.push(vertices[idx as usize] - normal * depth);
+            normals.push(normal);
+            sources.push(idx);
+            (vertices.len() - 1) as u32
+        })
+    };
+
+    let mut border_edges: Vec<_> = edge_uses
+        .into_values()
+        .filter(|&(_, _, uses)| uses == 1)
+        .map(|(a, b, _)| (a, b))
+        .collect();
+    // Keep the mesh deterministic regardless of hashing
+    border_edges.sort_unstable();
+
+    for (a, b) in border_edges {
+        let skirt_a = skirt_vertex(a);
+        let skirt_b = skirt_vertex(b);
+        // Reverse the border edge so skirts wind the same way as the triangle they hang from
+        indices.extend([b, a, skirt_a, b, skirt_a, skirt_b]);
+    }
+    sources
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+
+use super::{density_gradient, dual_mesh, MeshData, Mesher};
+use crate::generation::{tables::CUBE_CORNERS, DensityFunction, Point, TerrainChunk};
+
+/// Dual contouring, every cube crossing the surface gets a single vertex
+/// that best fits the tangent planes at the edge crossings, which keeps sharp features
+#[derive(Debug, Clone, Copy)]
+pub struct DualContouring {
+    /// Directions with eigenvalues smaller than this fraction of the largest one
+    /// are considered unconstrained, higher values snap vertices towards the crossings average
+    pub tolerance: f32,
+}
+
+impl Default for DualContouring {
+    fn default() -> Self {
+        Self { tolerance: 0.1f32 }
+    }
+}
+
+impl Mesher for DualContouring {
+    /// Normals come from the density function when it's given,
+    /// otherwise from the trilinear interpolation of the cube corners
+    fn mesh(
+        &self,
+        chunk: &TerrainChunk,
+        isolevel: f32,
+        density: Option<&dyn DensityFunction>,
+    ) -> MeshData {
+        let step = chunk.cube_size.min_element() * 0.01f32;
+        dual_mesh(chunk, isolevel, |cube, crossings| {
+            let normals = crossings.iter().map(|&position| match density {
+                Some(density) => density_gradient(density, position, step),
+                None => trilinear_gradient(cube, position),
+            });
+            let vertex = solve_qef(crossings, normals, self.tolerance);
+            // Vertices leaving their cube fold the mesh over itself
+            vertex.clamp(cube[0].position, cube[6].position)
+        })
+    }
+}
+
+/// Gradient of the values interpolated between the cube corners
+fn trilinear_gradient(cube: &[Point; 8], pos: Vec3) -> Vec3 {
+    let min = cube[0].position;
+    let size = cube[6].position - min;
+    let t = (pos - min) / size;
+
+    let mut gradient = Vec3::ZERO;
+    for (corner, offset) in cube.iter().zip(CUBE_CORNERS) {
+        // Weight of the corner along each axis and its derivative
+        let weight = |axis: usize| {
+            if offset[axis] == 1 {
+                t[axis]
+            } else {
+                1f32 - t[axis]
+            }
+        };
+        let sign = |axis: usize| if offset[axis] == 1 { 1f32 } else { -1f32 };
+        gradient += corner.value
+            * Vec3::new(
+                sign(0) * weight(1) * weight(2),
+                weight(0) * sign(1) * weight(2),
+                weight(0) * weight(1) * sign(2),
+            );
+    }
+    (gradient / size).normalize_or_zero()
+}
+
+/// Point minimizing the squared distances to the planes through `positions`
+/// perpendicular to `normals`, solved relative to the positions average
+/// so that unconstrained directions stay close to it
+fn solve_qef(positions: &[Vec3], normals: impl Iterator<Item = Vec3>, tolerance: f32) -> Vec3 {
+    let mass_point = positions.iter().sum::<Vec3>() / positions.len() as f32;
+
+    let mut ata = [[0f32; 3]; 3];
+    let mut atb = Vec3::ZERO;
+    for (position, normal) in positions.iter().zip(normals) {
+        for row in 0..3 {
+            for col in 0..3 {
+                ata[row][col] += normal[row] * normal[col];
+            }
+        }
+        atb += normal * normal.dot(*position - mass_point);
+    }
+
+    let (values, vectors) = symmetric_eigen(ata);
+    let max_value = values.abs().max_element();
+    let mut offset = Vec3::ZERO;
+    for i in 0..3 {
+        if values[i].abs() > tolerance * max_value {
+            let vector = vectors.col(i);
+            offset += vector * vector.dot(atb) / values[i];
+        }
+    }
+    mass_point + offset
+}
+
+/// Eigenvalues and eigenvectors (as columns) of a symmetric matrix using Jacobi rotations
+fn symmetric_eigen(mut a: [[f32; 3]; 3]) -> (Vec3, Mat3) {
+    const SWEEPS: usize = 8;
+
+    let mut v = [[1f32, 0f32, 0f32], [0f32, 1f32, 0f32], [0f32, 0f32, 1f32]];
+    for _ in 0..SWEEPS {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1e-12f32 {
+                continue;
+            }
+            // Rotation zeroing `a[p][q]`
+            let theta = (a[q][q] - a[p][p]) / (2f32 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1f32).sqrt());
+            let c = 1f32 / (t * t + 1f32).sqrt();
+            let s = t * c;
+
+            for row in a.iter_mut().chain(v.iter_mut()) {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+            let (row_p, row_q) = (Vec3::from(a[p]), Vec3::from(a[q]));
+            a[p] = (c * row_p - s * row_q).into();
+            a[q] = (s * row_p + c * row_q).into();
+        }
+    }
+
+    (
+        Vec3::new(a[0][0], a[1][1], a[2][2]),
+        Mat3::from_cols_array_2d(&v).transpose(),
+    )
+}
@@ -0,0 +1,72 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{MeshData, Mesher};
+use crate::generation::{
+    tables::*,
+    utils::{vertex_lerp, vertex_material},
+    DensityFunction, TerrainChunk,
+};
+
+/// Classic marching cubes using the original 256 configurations table
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MarchingCubes;
+
+impl Mesher for MarchingCubes {
+    fn mesh(
+        &self,
+        chunk: &TerrainChunk,
+        isolevel: f32,
+        _density: Option<&dyn DensityFunction>,
+    ) -> MeshData {
+        // Go throught all of the points except for the final in each dimension
+        // This way we get only 0th point of every cube in chunk
+        let mut mesh = MeshData::default();
+        // Vertices are identified by the pair of points on the edge they lie on,
+        // this way vertices shared between cubes are welded exactly
+        let mut edge_vertices = HashMap::new();
+        // Including the cubes around the chunk for the padding triangles
+        for z in -1..=chunk.size.z as i32 {
+            for y in -1..=chunk.size.y as i32 {
+                for x in -1..=chunk.size.x as i32 {
+                    let cube_idx = IVec3::new(x, y, z);
+                    let corners =
+                        CUBE_CORNERS.map(|offset| chunk.point_index(cube_idx + offset.as_ivec3()));
+                    let cube = corners.map(|idx| chunk.points[idx as usize]);
+
+                    // Compute cube configuration index by setting bits of the points that are below
+                    // the isosurface to 1
+                    let mut cube_index = 0;
+                    for (i, point) in cube.iter().enumerate() {
+                        if point.value < isolevel {
+                            cube_index |= 1 << i;
+                        }
+                    }
+
+                    // Get intersecred edges for the cube configuration,
+                    // calculate points along them
+                    let intersected_edges = INTERSECTED_EDGES[cube_index];
+                    for edge in intersected_edges {
+                        if edge == -1 {
+                            break;
+                        }
+                        let (p1_idx, p2_idx) = EDGE_VERTICES[edge as usize];
+                        let (p1_idx, p2_idx) = (p1_idx as usize, p2_idx as usize);
+                        let edge_key = (
+                            corners[p1_idx].min(corners[p2_idx]),
+                            corners[p1_idx].max(corners[p2_idx]),
+                        );
+                        let idx = *edge_vertices.entry(edge_key).or_insert_with(|| {
+                            let (p1, p2) = (cube[p1_idx], cube[p2_idx]);
+                            mesh.push_vertex(
+                                vertex_lerp(isolevel, p1, p2),
+                                vertex_material(isolevel, p1, p2),
+                            )
+                        });
+                        mesh.triangles(chunk, cube_idx).push(idx);
+                    }
+                }
+            }
+        }
+        mesh
+    }
+}
@@ -0,0 +1,494 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{MeshData, Mesher};
+use crate::generation::{
+    tables::*,
+    utils::{vertex_lerp, vertex_material},
+    DensityFunction, TerrainChunk,
+};
+
+/// Marching cubes resolving ambiguous configurations the way Marching Cubes 33 does.
+/// Instead of looking the triangulation up in the extended case tables it is built for every cube
+/// out of the contours on its faces, ambiguous faces are resolved with the asymptotic decider
+/// and body saddles of the trilinear interpolation decide which contours are joined
+/// by a tunnel. Faces are decided the same way by both cubes sharing them, so the mesh has no holes.
+/// Support is partial: tunnels branching out into three contours aren't built, and cubes
+/// whose contours can't be traced fall back to the plain marching cubes configuration
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MarchingCubes33;
+
+/// Point where the surface crosses a cube edge, given by the edge's cube vertices in ascending order
+type Crossing = (u8, u8);
+
+impl Mesher for MarchingCubes33 {
+    fn mesh(
+        &self,
+        chunk: &TerrainChunk,
+        isolevel: f32,
+        _density: Option<&dyn DensityFunction>,
+    ) -> MeshData {
+        let mut mesh = MeshData::default();
+        // Vertices are identified by the pair of points on the edge they lie on,
+        // the same way as in marching cubes
+        let mut edge_vertices = HashMap::new();
+        // Including the cubes around the chunk for the padding triangles
+        for z in -1..=chunk.size.z as i32 {
+            for y in -1..=chunk.size.y as i32 {
+                for x in -1..=chunk.size.x as i32 {
+                    let cube_idx = IVec3::new(x, y, z);
+                    let corners =
+                        CUBE_CORNERS.map(|offset| chunk.point_index(cube_idx + offset.as_ivec3()));
+                    let cube = corners.map(|idx| chunk.points[idx as usize]);
+                    let values = cube.map(|point| point.value - isolevel);
+                    if values
+                        .iter()
+                        .all(|&value| (value < 0f32) == (values[0] < 0f32))
+                    {
+                        continue;
+                    }
+
+                    let mut vertex = |(p1_idx, p2_idx): Crossing| {
+                        let (p1_idx, p2_idx) = (p1_idx as usize, p2_idx as usize);
+                        let edge_key = (
+                            corners[p1_idx].min(corners[p2_idx]),
+                            corners[p1_idx].max(corners[p2_idx]),
+                        );
+                        *edge_vertices.entry(edge_key).or_insert_with(|| {
+                            let (p1, p2) = (cube[p1_idx], cube[p2_idx]);
+                            mesh.push_vertex(
+                                vertex_lerp(isolevel, p1, p2),
+                                vertex_material(isolevel, p1, p2),
+                            )
+                        })
+                    };
+
+                    let mut triangles = vec![];
+                    let mut regions = Regions::new(&values);
+                    let triangulation = face_contours(&values, &mut regions).and_then(|contours| {
+                        let pairs = if contours.len() >= 2 {
+                            tunnels(&values, &contours, &regions)?
+                        } else {
+                            vec![]
+                        };
+                        Some((contours, pairs))
+                    });
+                    let Some((contours, pairs)) = triangulation else {
+                        // Values the contours can't be traced through, e.g. NaN,
+                        // get the plain marching cubes configuration
+                        let cube_index = values
+                            .iter()
+                            .enumerate()
+                            .filter(|&(_, &value)| value < 0f32)
+                            .fold(0, |index, (i, _)| index | 1 << i);
+                        for edge in INTERSECTED_EDGES[cube_index] {
+                            if edge == -1 {
+                                break;
+                            }
+                            triangles.push(vertex(EDGE_VERTICES[edge as usize]));
+                        }
+                        mesh.triangles(chunk, cube_idx).extend(triangles);
+                        continue;
+                    };
+                    let contour_vertices: Vec<Vec<u32>> = contours
+                        .iter()
+                        .map(|contour| contour.iter().map(|&crossing| vertex(crossing)).collect())
+                        .collect();
+
+                    let mut in_tunnel = vec![false; contours.len()];
+                    for (i, j) in pairs {
+                        tube(
+                            &contour_vertices[i],
+                            &contour_vertices[j],
+                            &mesh.positions,
+                            &mut triangles,
+                        );
+                        in_tunnel[i] = true;
+                        in_tunnel[j] = true;
+                    }
+                    for (i, (contour, vertices)) in
+                        contours.iter().zip(&contour_vertices).enumerate()
+                    {
+                        if !in_tunnel[i] {
+                            fan(contour, vertices, &mut mesh, &mut triangles);
+                        }
+                    }
+                    mesh.triangles(chunk, cube_idx).extend(triangles);
+                }
+            }
+        }
+        mesh
+    }
+}
+
+/// Triangulate a contour as a fan around one of its vertices, the fan's diagonals
+/// must not lie on cube faces since the neighbouring cube may put its own edge there,
+/// contours without a suitable vertex are fanned around their center instead
+fn fan(contour: &[Crossing], vertices: &[u32], mesh: &mut MeshData, indices: &mut Vec<u32>) {
+    let on_same_face = |(a, b): Crossing, (c, d): Crossing| {
+        CUBE_FACES
+            .iter()
+            .any(|face| [a, b, c, d].iter().all(|vertex| face.contains(vertex)))
+    };
+    let n = contour.len();
+    let apex = (0..n)
+        .find(|&apex| (2..n - 1).all(|i| !on_same_face(contour[apex], contour[(apex + i) % n])));
+
+    match apex {
+        Some(apex) => {
+            for i in 1..n - 1 {
+                indices.extend([
+                    vertices[apex],
+                    vertices[(apex + i) % n],
+                    vertices[(apex + i + 1) % n],
+                ]);
+            }
+        }
+        None => {
+            let center = vertices
+                .iter()
+                .map(|&idx| mesh.positions[idx as usize])
+                .sum::<Vec3>()
+                / n as f32;
+            let center = mesh.push_vertex(center, mesh.materials[vertices[0] as usize]);
+            for i in 0..n {
+                indices.extend([center, vertices[i], vertices[(i + 1) % n]]);
+            }
+        }
+    }
+}
+
+/// Parts of the cube surface with the same sign, cube vertices are joined
+/// along edges that don't cross the surface and along connected diagonals of ambiguous faces
+struct Regions {
+    solid: [bool; 8],
+    parents: [u8; 8],
+}
+
+impl Regions {
+    fn new(values: &[f32; 8]) -> Self {
+        let mut regions = Self {
+            solid: values.map(|value| value < 0f32),
+            parents: [0, 1, 2, 3, 4, 5, 6, 7],
+        };
+        for (p1_idx, p2_idx) in EDGE_VERTICES {
+            if regions.solid[p1_idx as usize] == regions.solid[p2_idx as usize] {
+                regions.join(p1_idx, p2_idx);
+            }
+        }
+        regions
+    }
+
+    fn find(&self, mut vertex: u8) -> u8 {
+        while self.parents[vertex as usize] != vertex {
+            vertex = self.parents[vertex as usize];
+        }
+        vertex
+    }
+
+    fn join(&mut self, a: u8, b: u8) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a as usize] = b;
+    }
+
+    /// Regions on both sides of the contour as a bit set of their roots
+    fn around(&self, contour: &[Crossing]) -> u8 {
+        contour.iter().fold(0, |set, &(a, b)| {
+            set | 1 << self.find(a) | 1 << self.find(b)
+        })
+    }
+}
+
+/// Closed contours of the surface on the cube faces, going counter clockwise
+/// when looking at them from the empty side, `None` if they don't close up
+fn face_contours(values: &[f32; 8], regions: &mut Regions) -> Option<Vec<Vec<Crossing>>> {
+    let solid = regions.solid;
+    let crossing = |a: u8, b: u8| (a.min(b), a.max(b));
+
+    // Every segment goes from the crossing where walking counter clockwise around the face
+    // enters the solid to the one where it leaves it
+    let mut segments = Vec::with_capacity(EDGE_VERTICES.len());
+    for face in CUBE_FACES {
+        let edge = |k: usize| crossing(face[k % 4], face[(k + 1) % 4]);
+        let is_solid = |k: usize| solid[face[k % 4] as usize];
+        let crossed: Vec<usize> = (0..4).filter(|&k| is_solid(k) != is_solid(k + 1)).collect();
+
+        match crossed[..] {
+            [first, second] => {
+                if is_solid(first) {
+                    segments.push((edge(second), edge(first)));
+                } else {
+                    segments.push((edge(first), edge(second)));
+                }
+            }
+            [_, _, _, _] => {
+                // Asymptotic decider, the diagonal with the bigger product of values
+                // is connected through the saddle of the bilinear interpolation
+                let product = |k: usize| values[face[k] as usize] * values[face[k + 2] as usize];
+                let solid_diagonal = if is_solid(0) { 0 } else { 1 };
+                let empty_diagonal = 1 - solid_diagonal;
+                let (connected, cut) = if product(solid_diagonal) >= product(empty_diagonal) {
+                    (solid_diagonal, empty_diagonal)
+                } else {
+                    (empty_diagonal, solid_diagonal)
+                };
+                regions.join(face[connected], face[connected + 2]);
+
+                // Segments cut off the corners of the other diagonal
+                for k in [cut, cut + 2] {
+                    let (before, after) = (edge(k + 3), edge(k));
+                    if is_solid(k) {
+                        segments.push((before, after));
+                    } else {
+                        segments.push((after, before));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut contours = vec![];
+    while let Some((start, mut end)) = segments.pop() {
+        let mut contour = vec![start];
+        while end != start {
+            let next = segments.iter().position(|&(from, _)| from == end)?;
+            let (from, to) = segments.swap_remove(next);
+            contour.push(from);
+            end = to;
+        }
+        contours.push(contour);
+    }
+    Some(contours)
+}
+
+/// Pairs of contours joined by a tunnel. Every pair is tested, two contours are joined
+/// when the regions they cut off from the cube surface are connected through the inside
+/// of the cube. That happens through a body saddle of the trilinear interpolation with the sign
+/// of the regions, walking away from the saddle along the tunnel leads into both of them.
+/// Tunnels branching out into three contours aren't resolved, those contours stay separate.
+/// `None` if a walk doesn't end at a cube vertex
+fn tunnels(
+    values: &[f32; 8],
+    contours: &[Vec<Crossing>],
+    regions: &Regions,
+) -> Option<Vec<(usize, usize)>> {
+    let trilinear = Trilinear::new(values);
+    let mut pairs = Vec::new();
+    let mut joined = vec![false; contours.len()];
+    for saddle in trilinear.saddles() {
+        let saddle_value = trilinear.value(saddle);
+        let tunnel_solid = saddle_value < 0f32;
+        // Along the tunnel the saddle is the highest point of a solid tunnel
+        // and the lowest one of an empty tunnel
+        let mut ends = 0u8;
+        for direction in WALK_DIRECTIONS {
+            let start = (saddle + direction.normalize() * SADDLE_STEP).clamp(Vec3::ZERO, Vec3::ONE);
+            let value = trilinear.value(start);
+            if (value < saddle_value) != tunnel_solid || value == saddle_value {
+                continue;
+            }
+            let corner = trilinear.walk_to_corner(start, !tunnel_solid)?;
+            if regions.solid[corner as usize] == tunnel_solid {
+                ends |= 1 << regions.find(corner);
+            }
+        }
+        if ends.count_ones() != 2 {
+            continue;
+        }
+
+        // Each of the contours goes around one end of the tunnel,
+        // the region between them is the one the tunnel passes through
+        let pair = (0..contours.len())
+            .flat_map(|i| (i + 1..contours.len()).map(move |j| (i, j)))
+            .find(|&(i, j)| {
+                let first = regions.around(&contours[i]);
+                let second = regions.around(&contours[j]);
+                let shared = first & second;
+                !joined[i]
+                    && !joined[j]
+                    && shared.count_ones() == 1
+                    && regions.solid[shared.trailing_zeros() as usize] != tunnel_solid
+                    && (first & ends) != (second & ends)
+                    && (first | second) & ends == ends
+            });
+        if let Some((i, j)) = pair {
+            joined[i] = true;
+            joined[j] = true;
+            pairs.push((i, j));
+        }
+    }
+    Some(pairs)
+}
+
+/// Distance of the first step away from a saddle, and of every step of the walks
+const SADDLE_STEP: f32 = 0.01;
+
+/// Directions to leave a saddle in, towards the corners, edges and faces of the cube
+const WALK_DIRECTIONS: [Vec3; 26] = {
+    let mut directions = [Vec3::ZERO; 26];
+    let mut i = 0;
+    let mut n = 0;
+    while n < 27 {
+        if n != 13 {
+            directions[i] = Vec3::new(
+                (n % 3) as f32 - 1f32,
+                (n / 3 % 3) as f32 - 1f32,
+                (n / 9) as f32 - 1f32,
+            );
+            i += 1;
+        }
+        n += 1;
+    }
+    directions
+};
+
+/// Trilinear interpolation of the values at the cube vertices,
+/// `f = c + cx x + cy y + cz z + cxy x y + cxz x z + cyz y z + cxyz x y z`
+struct Trilinear {
+    c: f32,
+    cx: f32,
+    cy: f32,
+    cz: f32,
+    cxy: f32,
+    cxz: f32,
+    cyz: f32,
+    cxyz: f32,
+}
+
+impl Trilinear {
+    fn new(values: &[f32; 8]) -> Self {
+        let mut v = [[[0f32; 2]; 2]; 2];
+        for (value, offset) in values.iter().zip(CUBE_CORNERS) {
+            v[offset.x as usize][offset.y as usize][offset.z as usize] = *value;
+        }
+
+        let c = v[0][0][0];
+        Self {
+            c,
+            cx: v[1][0][0] - c,
+            cy: v[0][1][0] - c,
+            cz: v[0][0][1] - c,
+            cxy: v[1][1][0] - v[1][0][0] - v[0][1][0] + c,
+            cxz: v[1][0][1] - v[1][0][0] - v[0][0][1] + c,
+            cyz: v[0][1][1] - v[0][1][0] - v[0][0][1] + c,
+            cxyz: v[1][1][1] - v[1][1][0] - v[1][0][1] - v[0][1][1]
+                + v[1][0][0]
+                + v[0][1][0]
+                + v[0][0][1]
+                - c,
+        }
+    }
+
+    fn value(&self, p: Vec3) -> f32 {
+        self.c
+            + self.cx * p.x
+            + self.cy * p.y
+            + self.cz * p.z
+            + self.cxy * p.x * p.y
+            + self.cxz * p.x * p.z
+            + self.cyz * p.y * p.z
+            + self.cxyz * p.x * p.y * p.z
+    }
+
+    fn gradient(&self, p: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cx + self.cxy * p.y + self.cxz * p.z + self.cxyz * p.y * p.z,
+            self.cy + self.cxy * p.x + self.cyz * p.z + self.cxyz * p.x * p.z,
+            self.cz + self.cxz * p.x + self.cyz * p.y + self.cxyz * p.x * p.y,
+        )
+    }
+
+    /// Saddle points inside the cube
+    fn saddles(&self) -> Vec<Vec3> {
+        let inside = |p: &Vec3| p.cmpgt(Vec3::ZERO).all() && p.cmplt(Vec3::ONE).all();
+        let (cx, cy, cz) = (self.cx, self.cy, self.cz);
+        let (cxy, cxz, cyz, cxyz) = (self.cxy, self.cxz, self.cyz, self.cxyz);
+
+        // Without the cubic term the gradient is linear and there is at most one saddle
+        if cxyz.abs() < f32::EPSILON {
+            let gradient = Mat3::from_cols_array(&[0f32, cxy, cxz, cxy, 0f32, cyz, cxz, cyz, 0f32]);
+            if gradient.determinant().abs() < f32::EPSILON {
+                return Vec::new();
+            }
+            let saddle = gradient.inverse() * -Vec3::new(cx, cy, cz);
+            return [saddle].into_iter().filter(inside).collect();
+        }
+
+        // Shifted by `(cyz, cxz, cxy) / cxyz` the gradient equations become
+        // `v w = vw`, `u w = uw` and `u v = uv`
+        let k2 = cxyz * cxyz;
+        let vw = (cxy * cxz - cxyz * cx) / k2;
+        let uw = (cxy * cyz - cxyz * cy) / k2;
+        let uv = (cxz * cyz - cxyz * cz) / k2;
+        let u_squared = uw * uv / vw;
+        if u_squared.is_nan() || u_squared <= 0f32 {
+            return Vec::new();
+        }
+
+        let u = u_squared.sqrt();
+        [u, -u]
+            .map(|u| Vec3::new(u - cyz / cxyz, uv / u - cxz / cxyz, uw / u - cxy / cxyz))
+            .into_iter()
+            .filter(inside)
+            .collect()
+    }
+
+    /// Cube vertex reached by walking down the interpolation from `start`, or up for `upwards`.
+    /// The interpolation is harmonic, so the walk can only end at a vertex unless the values aren't finite
+    fn walk_to_corner(&self, start: Vec3, upwards: bool) -> Option<u8> {
+        let mut p = start;
+        for _ in 0..(4f32 / SADDLE_STEP) as usize {
+            let mut direction = if upwards {
+                self.gradient(p)
+            } else {
+                -self.gradient(p)
+            };
+            // Slide along the faces instead of leaving the cube
+            for axis in 0..3 {
+                if (p[axis] <= 0f32 && direction[axis] < 0f32)
+                    || (p[axis] >= 1f32 && direction[axis] > 0f32)
+                {
+                    direction[axis] = 0f32;
+                }
+            }
+            if direction.length_squared() < f32::EPSILON * f32::EPSILON {
+                break;
+            }
+            p = (p + direction.normalize() * SADDLE_STEP).clamp(Vec3::ZERO, Vec3::ONE);
+        }
+        if !p.is_finite() {
+            return None;
+        }
+        let corner = p.round().as_uvec3();
+        CUBE_CORNERS
+            .iter()
+            .position(|&offset| offset == corner)
+            .map(|corner| corner as u8)
+    }
+}
+
+/// Triangulate a tube between two contours, they wind the same way around the surface,
+/// so the tube walks them in opposite directions
+fn tube(first: &[u32], second: &[u32], positions: &[Vec3], indices: &mut Vec<u32>) {
+    let second: Vec<u32> = second.iter().rev().copied().collect();
+    let distance = |a: u32, b: u32| positions[a as usize].distance_squared(positions[b as usize]);
+    let (n, m) = (first.len(), second.len());
+    let start = (0..m)
+        .min_by(|&x, &y| distance(first[0], second[x]).total_cmp(&distance(first[0], second[y])))
+        .unwrap_or_default();
+    let a = |i: usize| first[i % n];
+    let b = |j: usize| second[(start + j) % m];
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let advance_first =
+            j == m || (i < n && distance(a(i + 1), b(j)) <= distance(a(i), b(j + 1)));
+        if advance_first {
+            indices.extend([a(i), a(i + 1), b(j)]);
+            i += 1;
+        } else {
+            indices.extend([b(j + 1), b(j), a(i)]);
+            j += 1;
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{MeshData, Mesher};
+use crate::generation::{
+    tables::*,
+    utils::{vertex_lerp, vertex_material},
+    DensityFunction, TerrainChunk,
+};
+
+/// Marching tetrahedra, every cube is split into six tetrahedra which have no ambiguous
+/// configurations, so the mesh has no holes at the cost of more triangles
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MarchingTetrahedra;
+
+impl Mesher for MarchingTetrahedra {
+    fn mesh(
+        &self,
+        chunk: &TerrainChunk,
+        isolevel: f32,
+        _density: Option<&dyn DensityFunction>,
+    ) -> MeshData {
+        let mut mesh = MeshData::default();
+        // Vertices are identified by the pair of points on the edge they lie on,
+        // the same way as in marching cubes
+        let mut edge_vertices = HashMap::new();
+        // Including the cubes around the chunk for the padding triangles
+        for z in -1..=chunk.size.z as i32 {
+            for y in -1..=chunk.size.y as i32 {
+                for x in -1..=chunk.size.x as i32 {
+                    let cube_idx = IVec3::new(x, y, z);
+                    let corners =
+                        CUBE_CORNERS.map(|offset| chunk.point_index(cube_idx + offset.as_ivec3()));
+
+                    for tetrahedron in CUBE_TETRAHEDRA {
+                        let corners = tetrahedron.map(|vertex| corners[vertex as usize]);
+                        let points = corners.map(|idx| chunk.points[idx as usize]);
+
+                        let mut tetrahedron_index = 0;
+                        for (i, point) in points.iter().enumerate() {
+                            if point.value < isolevel {
+                                tetrahedron_index |= 1 << i;
+                            }
+                        }
+
+                        for edge in TETRAHEDRON_INTERSECTED_EDGES[tetrahedron_index] {
+                            if edge == -1 {
+                                break;
+                            }
+                            let (p1_idx, p2_idx) = TETRAHEDRON_EDGE_VERTICES[edge as usize];
+                            let (p1_idx, p2_idx) = (p1_idx as usize, p2_idx as usize);
+                            let edge_key = (
+                                corners[p1_idx].min(corners[p2_idx]),
+                                corners[p1_idx].max(corners[p2_idx]),
+                            );
+                            let idx = *edge_vertices.entry(edge_key).or_insert_with(|| {
+                                let (p1, p2) = (points[p1_idx], points[p2_idx]);
+                                mesh.push_vertex(
+                                    vertex_lerp(isolevel, p1, p2),
+                                    vertex_material(isolevel, p1, p2),
+                                )
+                            });
+                            mesh.triangles(chunk, cube_idx).push(idx);
+                        }
+                    }
+                }
+            }
+        }
+        mesh
+    }
+}
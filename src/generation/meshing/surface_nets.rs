@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+
+use super::{dual_mesh, MeshData, Mesher};
+use crate::generation::{DensityFunction, TerrainChunk};
+
+/// Naive surface nets, every cube crossing the surface gets a single vertex
+/// in the average of the edge crossings, which gives fewer and better shaped triangles
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SurfaceNets;
+
+impl Mesher for SurfaceNets {
+    fn mesh(
+        &self,
+        chunk: &TerrainChunk,
+        isolevel: f32,
+        _density: Option<&dyn DensityFunction>,
+    ) -> MeshData {
+        dual_mesh(chunk, isolevel, |_, crossings| {
+            crossings.iter().sum::<Vec3>() / crossings.len() as f32
+        })
+    }
+}
@@ -0,0 +1,231 @@
+//! Seeded coherent noise.
+//!
+//! Every generator only uses integer hashing and scalar `f32` arithmetic that is exactly
+//! specified by IEEE 754, so the same seed produces bit-identical samples on every machine.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::density::DensityFunction;
+
+mod fractal;
+mod perlin;
+mod simplex;
+mod value;
+mod worley;
+
+pub use fractal::{Fractal, FractalKind, FractalSettings};
+pub use perlin::Perlin;
+pub use simplex::Simplex;
+pub use value::Value;
+pub use worley::Worley;
+
+/// Coherent noise that can be sampled in 2, 3 or 4 dimensions.
+/// Samples are in `[-1, 1]` range
+pub trait NoiseFn: Send + Sync {
+    fn sample_2d(&self, p: Vec2) -> f32;
+    fn sample_3d(&self, p: Vec3) -> f32;
+    fn sample_4d(&self, p: Vec4) -> f32;
+}
+
+impl<N: NoiseFn + ?Sized> NoiseFn for Box<N> {
+    fn sample_2d(&self, p: Vec2) -> f32 {
+        (**self).sample_2d(p)
+    }
+
+    fn sample_3d(&self, p: Vec3) -> f32 {
+        (**self).sample_3d(p)
+    }
+
+    fn sample_4d(&self, p: Vec4) -> f32 {
+        (**self).sample_4d(p)
+    }
+}
+
+/// Base noise algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NoiseKind {
+    Perlin,
+    #[default]
+    Simplex,
+    Value,
+    Worley,
+}
+
+impl NoiseKind {
+    pub fn build(self, seed: u32) -> Box<dyn NoiseFn> {
+        match self {
+            NoiseKind::Perlin => Box::new(Perlin::new(seed)),
+            NoiseKind::Simplex => Box::new(Simplex::new(seed)),
+            NoiseKind::Value => Box::new(Value::new(seed)),
+            NoiseKind::Worley => Box::new(Worley::new(seed)),
+        }
+    }
+}
+
+/// How noise is turned into terrain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NoiseDimensions {
+    /// Ground level is displaced by 2D noise sampled on the XZ plane
+    #[default]
+    Surface,
+    /// Ground level is displaced by 3D noise, allows overhangs and caves
+    Volume,
+}
+
+/// Density source that displaces a horizontal ground plane by noise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseDensity<N> {
+    pub noise: N,
+    pub dimensions: NoiseDimensions,
+    pub frequency: f32,
+    pub amplitude: f32,
+    pub ground_level: f32,
+}
+
+impl<N: NoiseFn> NoiseDensity<N> {
+    pub fn new(noise: N) -> Self {
+        Self {
+            noise,
+            dimensions: NoiseDimensions::Surface,
+            frequency: 0.05f32,
+            amplitude: 8f32,
+            ground_level: 0f32,
+        }
+    }
+}
+
+impl<N: NoiseFn> DensityFunction for NoiseDensity<N> {
+    fn sample(&self, pos: Vec3) -> f32 {
+        let noise = match self.dimensions {
+            NoiseDimensions::Surface => self
+                .noise
+                .sample_2d(Vec2::new(pos.x, pos.z) * self.frequency),
+            NoiseDimensions::Volume => self.noise.sample_3d(pos * self.frequency),
+        };
+        pos.y - self.ground_level - self.amplitude * noise
+    }
+}
+
+/// Parameters of fractal noise terrain that can be edited at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseSettings {
+    pub noise: NoiseKind,
+    pub dimensions: NoiseDimensions,
+    pub frequency: f32,
+    pub amplitude: f32,
+    pub ground_level: f32,
+    pub fractal: FractalSettings,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        Self {
+            noise: NoiseKind::default(),
+            dimensions: NoiseDimensions::default(),
+            frequency: 0.05f32,
+            amplitude: 8f32,
+            ground_level: 0f32,
+            fractal: FractalSettings::default(),
+        }
+    }
+}
+
+impl NoiseSettings {
+    pub fn build(&self, seed: u32) -> NoiseDensity<Fractal<Box<dyn NoiseFn>>> {
+        NoiseDensity {
+            noise: Fractal::new(self.noise.build(seed), self.fractal),
+            dimensions: self.dimensions,
+            frequency: self.frequency,
+            amplitude: self.amplitude,
+            ground_level: self.ground_level,
+        }
+    }
+}
+
+const HASH_PRIMES: [u32; 4] = [0x8da6_b343, 0xd816_3841, 0xcb1a_b31f, 0x1656_67b1];
+
+/// Hash lattice coordinates together with a seed
+fn hash<const D: usize>(seed: u32, coords: [i32; D]) -> u32 {
+    let mut h = seed.wrapping_mul(0x9e37_79b9) ^ 0x85eb_ca6b;
+    for (c, prime) in coords.into_iter().zip(HASH_PRIMES) {
+        h ^= (c as u32).wrapping_mul(prime);
+        h = h.rotate_left(13).wrapping_mul(0x5bd1_e995);
+    }
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// Map a hash to `[0, 1]`
+fn hash_to_unit(h: u32) -> f32 {
+    // Keep 24 bits so the conversion is exact
+    (h >> 8) as f32 * (1f32 / 16_777_215f32)
+}
+
+/// Split coordinates into lattice cell and position inside of it
+fn split_cell<const D: usize>(p: [f32; D]) -> ([i32; D], [f32; D]) {
+    let mut cell = [0; D];
+    let mut local = [0f32; D];
+    for i in 0..D {
+        let floor = p[i].floor();
+        cell[i] = floor as i32;
+        local[i] = p[i] - floor;
+    }
+    (cell, local)
+}
+
+/// Quintic interpolation curve with zero first and second derivatives at the ends
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6f32 - 15f32) + 10f32)
+}
+
+/// Dot product of the offset with one of the lattice gradients picked by the hash
+fn gradient_dot<const D: usize>(h: u32, offset: [f32; D]) -> f32 {
+    match D {
+        // Eight directions: axes and diagonals
+        2 => {
+            let (x, y) = (offset[0], offset[1]);
+            match h & 7 {
+                0 => x + y,
+                1 => -x + y,
+                2 => x - y,
+                3 => -x - y,
+                4 => x,
+                5 => -x,
+                6 => y,
+                _ => -y,
+            }
+        }
+        // Twelve directions to the edge midpoints of a cube
+        3 => {
+            let (x, y, z) = (offset[0], offset[1], offset[2]);
+            let h = h % 12;
+            let (u, v) = match h {
+                0..=3 => (x, y),
+                4..=7 => (x, z),
+                _ => (y, z),
+            };
+            let u = if h & 1 == 0 { u } else { -u };
+            let v = if h & 2 == 0 { v } else { -v };
+            u + v
+        }
+        // Thirty two directions to the edge midpoints of a tesseract
+        _ => {
+            let (x, y, z, w) = (offset[0], offset[1], offset[2], offset[3]);
+            let (u, v, w) = match h & 31 {
+                0..=7 => (x, y, z),
+                8..=15 => (x, y, w),
+                16..=23 => (x, z, w),
+                _ => (y, z, w),
+            };
+            let u = if h & 1 == 0 { u } else { -u };
+            let v = if h & 2 == 0 { v } else { -v };
+            let w = if h & 4 == 0 { w } else { -w };
+            u + v + w
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::NoiseFn;
+
+/// How octaves of the base noise are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FractalKind {
+    /// Fractional Brownian motion, plain sum of octaves
+    #[default]
+    Fbm,
+    /// Ridged multifractal, sharp crests where the base noise crosses zero
+    Ridged,
+    /// Sum of absolute octaves, puffy rounded hills
+    Billow,
+    /// Sum of absolute octaves without recentering, creases at the bottom
+    Turbulence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FractalSettings {
+    pub kind: FractalKind,
+    pub octaves: u32,
+    /// Frequency multiplier between successive octaves
+    pub lacunarity: f32,
+    /// Amplitude multiplier between successive octaves
+    pub gain: f32,
+    /// Octave `i` is sampled with its coordinates shifted by `octave_offset * i`
+    /// so that octaves don't line up at the origin
+    pub octave_offset: Vec3,
+    /// Value the absolute noise is subtracted from in ridged multifractal,
+    /// higher values make ridges wider
+    pub ridge_offset: f32,
+}
+
+impl Default for FractalSettings {
+    fn default() -> Self {
+        Self {
+            kind: FractalKind::Fbm,
+            octaves: 4,
+            lacunarity: 2f32,
+            gain: 0.5f32,
+            octave_offset: Vec3::new(19.1f32, 33.4f32, 47.2f32),
+            ridge_offset: 1f32,
+        }
+    }
+}
+
+/// Several octaves of the base noise layered on top of each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fractal<N> {
+    pub noise: N,
+    pub settings: FractalSettings,
+}
+
+impl<N: NoiseFn> Fractal<N> {
+    pub fn new(noise: N, settings: FractalSettings) -> Self {
+        Self { noise, settings }
+    }
+
+    pub fn fbm(noise: N) -> Self {
+        Self::with_kind(noise, FractalKind::Fbm)
+    }
+
+    pub fn ridged(noise: N) -> Self {
+        Self::with_kind(noise, FractalKind::Ridged)
+    }
+
+    pub fn billow(noise: N) -> Self {
+        Self::with_kind(noise, FractalKind::Billow)
+    }
+
+    pub fn turbulence(noise: N) -> Self {
+        Self::with_kind(noise, FractalKind::Turbulence)
+    }
+
+    fn with_kind(noise: N, kind: FractalKind) -> Self {
+        Self::new(
+            noise,
+            FractalSettings {
+                kind,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Combine octaves, `sample(frequency, offset)` samples base noise
+    /// at coordinates scaled by frequency and shifted by offset
+    fn combine(&self, mut sample: impl FnMut(f32, Vec3) -> f32) -> f32 {
+        let settings = &self.settings;
+        let mut frequency = 1f32;
+        let mut amplitude = 1f32;
+        let mut total_amplitude = 0f32;
+        let mut sum = 0f32;
+        // Ridged multifractal weights every octave by the previous one
+        // so that details only appear on ridges
+        let mut weight = 1f32;
+
+        for octave in 0..settings.octaves.max(1) {
+            let noise = sample(frequency, settings.octave_offset * octave as f32);
+            let signal = match settings.kind {
+                FractalKind::Fbm => noise,
+                FractalKind::Billow => 2f32 * noise.abs() - 1f32,
+                FractalKind::Turbulence => noise.abs(),
+                FractalKind::Ridged => {
+                    let signal = settings.ridge_offset - noise.abs();
+                    let signal = signal * signal * weight;
+                    weight = (signal * settings.gain).clamp(0f32, 1f32);
+                    signal
+                }
+            };
+            sum += signal * amplitude;
+            total_amplitude += amplitude;
+            frequency *= settings.lacunarity;
+            amplitude *= settings.gain;
+        }
+
+        let normalized = sum / total_amplitude;
+        match settings.kind {
+            // Turbulence stays in `[0, 1]`, recentered it would be billow
+            FractalKind::Fbm | FractalKind::Billow | FractalKind::Turbulence => normalized,
+            FractalKind::Ridged => {
+                // The squared signal peaks where the noise is 0 or where its magnitude is 1
+                let offset = settings.ridge_offset;
+                let max = (offset * offset).max((1f32 - offset) * (1f32 - offset));
+                2f32 * normalized / max - 1f32
+            }
+        }
+    }
+}
+
+impl<N: NoiseFn> NoiseFn for Fractal<N> {
+    fn sample_2d(&self, p: Vec2) -> f32 {
+        self.combine(|frequency, offset| {
+            self.noise
+                .sample_2d(p * frequency + Vec2::new(offset.x, offset.y))
+        })
+    }
+
+    fn sample_3d(&self, p: Vec3) -> f32 {
+        self.combine(|frequency, offset| self.noise.sample_3d(p * frequency + offset))
+    }
+
+    fn sample_4d(&self, p: Vec4) -> f32 {
+        self.combine(|frequency, offset| self.noise.sample_4d(p * frequency + offset.extend(0f32)))
+    }
+}
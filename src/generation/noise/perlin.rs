@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+
+use super::{fade, gradient_dot, hash, split_cell, NoiseFn};
+
+/// Classic gradient noise with quintic interpolation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Perlin {
+    pub seed: u32,
+}
+
+impl Perlin {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    fn sample<const D: usize>(&self, p: [f32; D]) -> f32 {
+        let (cell, local) = split_cell(p);
+        let mut fades = [0f32; D];
+        for i in 0..D {
+            fades[i] = fade(local[i]);
+        }
+
+        // Blend gradient contributions of every lattice corner around the point
+        let mut result = 0f32;
+        for corner in 0..(1usize << D) {
+            let mut corner_cell = cell;
+            let mut offset = local;
+            let mut weight = 1f32;
+            for i in 0..D {
+                if corner & (1 << i) != 0 {
+                    corner_cell[i] = corner_cell[i].wrapping_add(1);
+                    offset[i] -= 1f32;
+                    weight *= fades[i];
+                } else {
+                    weight *= 1f32 - fades[i];
+                }
+            }
+            result += weight * gradient_dot(hash(self.seed, corner_cell), offset);
+        }
+        result.clamp(-1f32, 1f32)
+    }
+}
+
+impl NoiseFn for Perlin {
+    fn sample_2d(&self, p: Vec2) -> f32 {
+        self.sample(p.to_array())
+    }
+
+    fn sample_3d(&self, p: Vec3) -> f32 {
+        self.sample(p.to_array())
+    }
+
+    fn sample_4d(&self, p: Vec4) -> f32 {
+        self.sample(p.to_array())
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use super::{gradient_dot, hash, NoiseFn};
+
+/// Skewing and unskewing factors, `(sqrt(n + 1) - 1) / n` and `(1 - 1 / sqrt(n + 1)) / n`
+const F2: f32 = 0.366_025_42;
+const G2: f32 = 0.211_324_87;
+const F3: f32 = 1f32 / 3f32;
+const G3: f32 = 1f32 / 6f32;
+const F4: f32 = 0.309_017;
+const G4: f32 = 0.138_196_6;
+
+/// Gradient noise on a simplex grid, cheaper than [`super::Perlin`] in higher dimensions
+/// and without its axis-aligned artifacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Simplex {
+    pub seed: u32,
+}
+
+impl Simplex {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Contribution of a single simplex corner
+    fn corner<const D: usize>(&self, cell: [i32; D], offset: [f32; D], radius: f32) -> f32 {
+        let mut t = radius;
+        for o in offset {
+            t -= o * o;
+        }
+        if t < 0f32 {
+            return 0f32;
+        }
+        let t2 = t * t;
+        t2 * t2 * gradient_dot(hash(self.seed, cell), offset)
+    }
+}
+
+impl NoiseFn for Simplex {
+    fn sample_2d(&self, p: Vec2) -> f32 {
+        let (x, y) = (p.x, p.y);
+        let s = (x + y) * F2;
+        let i = (x + s).floor();
+        let j = (y + s).floor();
+        let t = (i + j) * G2;
+        let x0 = x - (i - t);
+        let y0 = y - (j - t);
+        let (i, j) = (i as i32, j as i32);
+
+        // Pick the triangle the point is in
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+        let x1 = x0 - i1 as f32 + G2;
+        let y1 = y0 - j1 as f32 + G2;
+        let x2 = x0 - 1f32 + 2f32 * G2;
+        let y2 = y0 - 1f32 + 2f32 * G2;
+
+        let n = self.corner([i, j], [x0, y0], 0.5f32)
+            + self.corner([i.wrapping_add(i1), j.wrapping_add(j1)], [x1, y1], 0.5f32)
+            + self.corner([i.wrapping_add(1), j.wrapping_add(1)], [x2, y2], 0.5f32);
+        (70f32 * n).clamp(-1f32, 1f32)
+    }
+
+    fn sample_3d(&self, p: Vec3) -> f32 {
+        let (x, y, z) = (p.x, p.y, p.z);
+        let s = (x + y + z) * F3;
+        let i = (x + s).floor();
+        let j = (y + s).floor();
+        let k = (z + s).floor();
+        let t = (i + j + k) * G3;
+        let x0 = x - (i - t);
+        let y0 = y - (j - t);
+        let z0 = z - (k - t);
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+
+        // Pick the tetrahedron the point is in by ordering the offsets
+        let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let corners = [
+            ((0, 0, 0), 0f32),
+            ((i1, j1, k1), G3),
+            ((i2, j2, k2), 2f32 * G3),
+            ((1, 1, 1), 3f32 * G3),
+        ];
+        let mut n = 0f32;
+        for ((ci, cj, ck), g) in corners {
+            n += self.corner(
+                [i.wrapping_add(ci), j.wrapping_add(cj), k.wrapping_add(ck)],
+                [x0 - ci as f32 + g, y0 - cj as f32 + g, z0 - ck as f32 + g],
+                0.6f32,
+            );
+        }
+        (32f32 * n).clamp(-1f32, 1f32)
+    }
+
+    fn sample_4d(&self, p: Vec4) -> f32 {
+        let (x, y, z, w) = (p.x, p.y, p.z, p.w);
+        let s = (x + y + z + w) * F4;
+        let i = (x + s).floor();
+        let j = (y + s).floor();
+        let k = (z + s).floor();
+        let l = (w + s).floor();
+        let t = (i + j + k + l) * G4;
+        let offset = [x - (i - t), y - (j - t), z - (k - t), w - (l - t)];
+        let cell = [i as i32, j as i32, k as i32, l as i32];
+
+        // Rank the offsets to find the simplex the point is in,
+        // corner `c` is displaced along every axis with a rank of at least `4 - c`
+        let mut rank = [0; 4];
+        for a in 0..4 {
+            for b in (a + 1)..4 {
+                if offset[a] > offset[b] {
+                    rank[a] += 1;
+                } else {
+                    rank[b] += 1;
+                }
+            }
+        }
+
+        let mut n = 0f32;
+        for c in 0..=4 {
+            let g = c as f32 * G4;
+            let mut corner_cell = cell;
+            let mut corner_offset = offset;
+            for axis in 0..4 {
+                let step = i32::from(rank[axis] + c >= 4);
+                corner_cell[axis] = corner_cell[axis].wrapping_add(step);
+                corner_offset[axis] = corner_offset[axis] - step as f32 + g;
+            }
+            n += self.corner(corner_cell, corner_offset, 0.6f32);
+        }
+        (27f32 * n).clamp(-1f32, 1f32)
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use super::{fade, hash, hash_to_unit, split_cell, NoiseFn};
+
+/// Random values at lattice points smoothly interpolated in between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Value {
+    pub seed: u32,
+}
+
+impl Value {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    fn sample<const D: usize>(&self, p: [f32; D]) -> f32 {
+        let (cell, local) = split_cell(p);
+        let mut fades = [0f32; D];
+        for i in 0..D {
+            fades[i] = fade(local[i]);
+        }
+
+        let mut result = 0f32;
+        for corner in 0..(1usize << D) {
+            let mut corner_cell = cell;
+            let mut weight = 1f32;
+            for i in 0..D {
+                if corner & (1 << i) != 0 {
+                    corner_cell[i] = corner_cell[i].wrapping_add(1);
+                    weight *= fades[i];
+                } else {
+                    weight *= 1f32 - fades[i];
+                }
+            }
+            result += weight * (hash_to_unit(hash(self.seed, corner_cell)) * 2f32 - 1f32);
+        }
+        result.clamp(-1f32, 1f32)
+    }
+}
+
+impl NoiseFn for Value {
+    fn sample_2d(&self, p: Vec2) -> f32 {
+        self.sample(p.to_array())
+    }
+
+    fn sample_3d(&self, p: Vec3) -> f32 {
+        self.sample(p.to_array())
+    }
+
+    fn sample_4d(&self, p: Vec4) -> f32 {
+        self.sample(p.to_array())
+    }
+}
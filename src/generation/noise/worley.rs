@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+use super::{hash, hash_to_unit, split_cell, NoiseFn};
+
+/// Cellular noise, distance to the closest of randomly placed feature points
+/// remapped from `[0, 1]` to `[-1, 1]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Worley {
+    pub seed: u32,
+}
+
+impl Worley {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    fn sample<const D: usize>(&self, p: [f32; D]) -> f32 {
+        let (cell, local) = split_cell(p);
+
+        // Every cell has one feature point, the closest one is in the 3^D neighbourhood
+        let mut closest = f32::MAX;
+        for neighbour in 0..3usize.pow(D as u32) {
+            let mut neighbour_cell = cell;
+            let mut offsets = [0f32; D];
+            let mut digits = neighbour;
+            for i in 0..D {
+                let offset = (digits % 3) as i32 - 1;
+                digits /= 3;
+                neighbour_cell[i] = neighbour_cell[i].wrapping_add(offset);
+                offsets[i] = offset as f32;
+            }
+
+            let mut distance = 0f32;
+            for i in 0..D {
+                let axis_seed = self
+                    .seed
+                    .wrapping_add((i as u32 + 1).wrapping_mul(0x68e3_1da4));
+                let feature = offsets[i] + hash_to_unit(hash(axis_seed, neighbour_cell));
+                let delta = feature - local[i];
+                distance += delta * delta;
+            }
+            closest = closest.min(distance);
+        }
+        (closest.sqrt() * 2f32 - 1f32).clamp(-1f32, 1f32)
+    }
+}
+
+impl NoiseFn for Worley {
+    fn sample_2d(&self, p: Vec2) -> f32 {
+        self.sample(p.to_array())
+    }
+
+    fn sample_3d(&self, p: Vec3) -> f32 {
+        self.sample(p.to_array())
+    }
+
+    fn sample_4d(&self, p: Vec4) -> f32 {
+        self.sample(p.to_array())
+    }
+}
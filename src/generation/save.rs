@@ -0,0 +1,290 @@
+//! Binary world files, the points of every chunk are stored so that edits survive
+//!
+//! Layout, all numbers are little endian:
+//! - magic bytes `TPGW` and the format version as `u32`
+//! - seed as `u32`, length of the config as `u32` and the config in RON
+//! - amount of chunks as `u32`, then for every chunk its coordinates as three `i32`,
+//!   its level of detail as `u32`, the compressed length as `u32` and the zlib compressed
+//!   point values as `f32` followed by the point materials as `u8`
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use super::{ConfigFormat, MaterialId, TerrainChunk, TerrainGeneratorConfig, CHUNK_PADDING};
+
+const MAGIC: &[u8; 4] = b"TPGW";
+
+// Limits that keep a corrupted file from allocating all of the memory
+const MAX_CONFIG_LEN: u32 = 1 << 20;
+const MAX_CHUNKS: u32 = 1 << 20;
+const MAX_CHUNK_POINTS: u64 = 1 << 24;
+
+/// Version written into new files, files of other versions are rejected
+pub const WORLD_FORMAT_VERSION: u32 = 1;
+
+/// Write the sampled chunks and the config into the file at `path`
+#[derive(Event, Debug, Clone)]
+pub struct SaveWorldEvent {
+    pub path: PathBuf,
+}
+
+/// Replace the chunks and the config with the ones saved in the file at `path`
+#[derive(Event, Debug, Clone)]
+pub struct LoadWorldEvent {
+    pub path: PathBuf,
+}
+
+/// Config and chunks read from a world file
+#[derive(Debug, Clone)]
+pub struct SavedWorld {
+    pub config: TerrainGeneratorConfig,
+    pub chunks: Vec<TerrainChunk>,
+}
+
+#[derive(Debug)]
+pub enum WorldFileError {
+    Io(io::Error),
+    /// The file doesn't start with the magic bytes
+    NotAWorld,
+    UnsupportedVersion(u32),
+    /// The file ends before all of the data it announces
+    Truncated,
+    Config(String),
+    TooManyChunks(u32),
+    /// The chunk's level of detail is above the config's levels
+    InvalidLod {
+        coord: IVec3,
+        lod: u32,
+    },
+    /// The chunk's points don't fit its size in the config
+    ChunkSize {
+        coord: IVec3,
+    },
+}
+
+impl fmt::Display for WorldFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldFileError::Io(err) => write!(f, "{err}"),
+            WorldFileError::NotAWorld => write!(f, "not a world file"),
+            WorldFileError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {version}, expected {WORLD_FORMAT_VERSION}"
+            ),
+            WorldFileError::Truncated => write!(f, "the file is truncated"),
+            WorldFileError::Config(err) => write!(f, "invalid config: {err}"),
+            WorldFileError::TooManyChunks(count) => {
+                write!(f, "{count} chunks are more than the limit of {MAX_CHUNKS}")
+            }
+            WorldFileError::InvalidLod { coord, lod } => {
+                write!(f, "chunk {coord} has an invalid level of detail {lod}")
+            }
+            WorldFileError::ChunkSize { coord } => {
+                write!(f, "points of chunk {coord} don't match the chunk size")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldFileError {}
+
+impl From<io::Error> for WorldFileError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            WorldFileError::Truncated
+        } else {
+            WorldFileError::Io(err)
+        }
+    }
+}
+
+/// Save the chunks generated with `config` into a new file at `path`
+pub fn save_world<'a>(
+    path: impl AsRef<Path>,
+    config: &TerrainGeneratorConfig,
+    chunks: impl IntoIterator<Item = &'a TerrainChunk>,
+) -> Result<(), WorldFileError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_world(&mut writer, config, chunks)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the world saved at `path`, chunks keep their points and only need to be meshed
+pub fn load_world(path: impl AsRef<Path>) -> Result<SavedWorld, WorldFileError> {
+    read_world(&mut BufReader::new(File::open(path)?))
+}
+
+pub fn write_world<'a>(
+    writer: &mut impl Write,
+    config: &TerrainGeneratorConfig,
+    chunks: impl IntoIterator<Item = &'a TerrainChunk>,
+) -> Result<(), WorldFileError> {
+    let config_text =
+        ron::to_string(config).map_err(|err| WorldFileError::Config(err.to_string()))?;
+    writer.write_all(MAGIC)?;
+    write_u32(writer, WORLD_FORMAT_VERSION)?;
+    write_u32(writer, config.seed)?;
+    write_u32(writer, config_text.len() as u32)?;
+    writer.write_all(config_text.as_bytes())?;
+
+    let chunks: Vec<_> = chunks.into_iter().collect();
+    write_u32(writer, chunks.len() as u32)?;
+    for chunk in chunks {
+        // Chunks shown at another level of detail are saved with their modified points
+        let chunk = chunk.stored.as_deref().unwrap_or(chunk);
+        for axis in chunk.coord.to_array() {
+            writer.write_all(&axis.to_le_bytes())?;
+        }
+        write_u32(writer, chunk.lod)?;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for point in &chunk.points {
+            encoder.write_all(&point.value.to_le_bytes())?;
+        }
+        let materials: Vec<u8> = chunk
+            .points
+            .iter()
+            .map(|point| point.material as u8)
+            .collect();
+        encoder.write_all(&materials)?;
+        let compressed = encoder.finish()?;
+        write_u32(writer, compressed.len() as u32)?;
+        writer.write_all(&compressed)?;
+    }
+    Ok(())
+}
+
+pub fn read_world(reader: &mut impl Read) -> Result<SavedWorld, WorldFileError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(WorldFileError::NotAWorld);
+    }
+    let version = read_u32(reader)?;
+    if version != WORLD_FORMAT_VERSION {
+        return Err(WorldFileError::UnsupportedVersion(version));
+    }
+
+    let seed = read_u32(reader)?;
+    let config_len = read_u32(reader)?;
+    if config_len > MAX_CONFIG_LEN {
+        return Err(WorldFileError::Config(format!(
+            "{config_len} bytes are more than the limit of {MAX_CONFIG_LEN}"
+        )));
+    }
+    let config_text = read_bytes(reader, config_len as u64)?;
+    let mut config: TerrainGeneratorConfig = std::str::from_utf8(&config_text)
+        .map_err(|err| err.to_string())
+        .and_then(|text| {
+            TerrainGeneratorConfig::from_text(text, ConfigFormat::Ron)
+                .map_err(|err| err.to_string())
+        })
+        .map_err(WorldFileError::Config)?;
+    config.seed = seed;
+    // Levels past the bits of the chunk size can't be shifted
+    if config.lod_levels >= u32::BITS {
+        return Err(WorldFileError::Config(format!(
+            "{} levels of detail are too many",
+            config.lod_levels
+        )));
+    }
+    if chunk_point_count(config.chunk_size, 0) > MAX_CHUNK_POINTS {
+        return Err(WorldFileError::Config(format!(
+            "chunks of size {} have too many points",
+            config.chunk_size
+        )));
+    }
+
+    let chunk_count = read_u32(reader)?;
+    if chunk_count > MAX_CHUNKS {
+        return Err(WorldFileError::TooManyChunks(chunk_count));
+    }
+    let mut chunks = Vec::new();
+    for _ in 0..chunk_count {
+        let mut coord = IVec3::ZERO;
+        for axis in 0..3 {
+            let mut bytes = [0u8; 4];
+            reader.read_exact(&mut bytes)?;
+            coord[axis] = i32::from_le_bytes(bytes);
+        }
+        let lod = read_u32(reader)?;
+        if lod > config.lod_levels {
+            return Err(WorldFileError::InvalidLod { coord, lod });
+        }
+
+        // Four bytes of value and one of material per point
+        let expected_len = chunk_point_count(config.chunk_size, lod) * 5;
+        let compressed_len = read_u32(reader)? as u64;
+        // Incompressible data grows a little in zlib
+        if compressed_len > expected_len + expected_len / 1000 + 64 {
+            return Err(WorldFileError::ChunkSize { coord });
+        }
+        let compressed = read_bytes(reader, compressed_len)?;
+        let mut bytes = Vec::new();
+        ZlibDecoder::new(compressed.as_slice())
+            .take(expected_len + 1)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != expected_len {
+            return Err(WorldFileError::ChunkSize { coord });
+        }
+
+        let mut chunk =
+            TerrainChunk::with_lod(coord, config.chunk_size, config.cube_edge_length, lod);
+        let (values, materials) = bytes.split_at(chunk.points.len() * 4);
+        for ((point, value), &material) in chunk
+            .points
+            .iter_mut()
+            .zip(values.chunks_exact(4))
+            .zip(materials)
+        {
+            point.value = f32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+            point.material = MaterialId::ALL
+                .get(material as usize)
+                .copied()
+                .unwrap_or_default();
+        }
+        // Loaded points may differ from the density function, they are kept like edits
+        chunk.modified = true;
+        chunks.push(chunk);
+    }
+
+    Ok(SavedWorld { config, chunks })
+}
+
+/// Amount of points of a chunk at the level of detail, padding included
+fn chunk_point_count(chunk_size: UVec3, lod: u32) -> u64 {
+    let size = (chunk_size >> lod).max(UVec3::ONE);
+    let point_size = size.as_u64vec3() + 1 + 2 * CHUNK_PADDING as u64;
+    point_size
+        .x
+        .saturating_mul(point_size.y)
+        .saturating_mul(point_size.z)
+}
+
+/// Read `len` bytes, the buffer only grows with the data that is actually there
+fn read_bytes(reader: &mut impl Read, len: u64) -> Result<Vec<u8>, WorldFileError> {
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(WorldFileError::Truncated);
+    }
+    Ok(bytes)
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -1,9 +1,9 @@
-use bevy::{
-    prelude::*,
-    render::mesh::{Indices, PrimitiveTopology},
-};
+use std::sync::Arc;
 
-use super::{tables::*, utils::*, *};
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool, utils::HashMap};
+use futures_lite::future;
+
+use super::*;
 
 pub(super) fn light(mut commands: Commands) {
     commands.spawn(DirectionalLightBundle {
@@ -22,178 +22,563 @@ pub(super) fn light(mut commands: Commands) {
     });
 }
 
+pub(super) fn setup_material(
+    mut commands: Commands,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+    mut triplanar_materials: ResMut<Assets<TriplanarMaterial>>,
+    config: Res<TerrainGeneratorConfig>,
+    asset_server: Res<AssetServer>,
+) {
+    let standard = standard_materials.add(terrain_standard_material());
+    let triplanar =
+        triplanar_materials.add(TriplanarMaterial::new(&config.splatting, &asset_server));
+    commands.insert_resource(TerrainMaterial {
+        standard,
+        triplanar,
+    });
+}
+
+/// Apply the splatting settings to the triplanar material
+pub(super) fn update_material(
+    material: Res<TerrainMaterial>,
+    mut triplanar_materials: ResMut<Assets<TriplanarMaterial>>,
+    config: Res<TerrainGeneratorConfig>,
+    asset_server: Res<AssetServer>,
+) {
+    if let Some(triplanar) = triplanar_materials.get_mut(&material.triplanar) {
+        *triplanar = TriplanarMaterial::new(&config.splatting, &asset_server);
+    }
+}
+
+pub(super) fn update_density(
+    config: Res<TerrainGeneratorConfig>,
+    mut density: ResMut<TerrainDensity>,
+) {
+    match config.density.build(config.seed) {
+        Ok(Some(new_density)) => {
+            info!("Rebuilding density function");
+            *density = new_density;
+        }
+        Ok(None) => {}
+        Err(err) => error!("Failed to build density function, keeping the old one: {err}"),
+    }
+}
+
 pub(super) fn create_chunks(
     mut commands: Commands,
     existing_chunks: Query<Option<Entity>, With<TerrainChunk>>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut stored_chunks: ResMut<StoredChunks>,
+    mut history: ResMut<EditHistory>,
+    viewers: Query<(&TerrainViewer, &GlobalTransform)>,
     config: Res<TerrainGeneratorConfig>,
 ) {
+    // New points can't be edited back into the old ones
+    history.clear();
+    stored_chunks.0.clear();
+
     info!("Despawning chunks");
     for existing_chunk_entity in existing_chunks.iter().flatten() {
         debug!("Despawning chunk '{existing_chunk_entity:?}'");
         commands.entity(existing_chunk_entity).despawn();
     }
+    chunk_map.0.clear();
+
+    if config.streaming {
+        info!("Streaming chunks with config:\n{config:#?}");
+        return;
+    }
 
     info!("Generating chunks with config:\n{config:#?}");
-    let chunks_amount = config.chunks_amount;
-    let chunk_size = config.chunk_size;
-    let cube_size = config.cube_edge_length;
-    let mut chunks = vec![];
+    let focuses = viewer_focuses(&viewers);
+    let chunks_amount = config.chunks_amount.as_ivec3();
     for z in 0..chunks_amount.z {
         for y in 0..chunks_amount.y {
             for x in 0..chunks_amount.x {
-                let chunk = TerrainChunk::new(
-                    Vec3 {
-                        x: (x * chunk_size.x) as f32 * cube_size,
-                        y: (y * chunk_size.y) as f32 * cube_size,
-                        z: (z * chunk_size.z) as f32 * cube_size,
-                    },
-                    config.chunk_size,
-                    config.cube_edge_length,
-                );
-                chunks.push(chunk);
+                let coord = IVec3::new(x, y, z);
+                let lod = chunk_lod(coord, &focuses, &config);
+                spawn_chunk(&mut commands, &mut chunk_map, coord, lod, &config);
             }
         }
     }
-    commands.spawn_batch(chunks);
 }
 
-pub(super) fn appply_ground_function(
-    mut new_chunks: Query<(Entity, &mut TerrainChunk), Added<TerrainChunk>>,
+fn spawn_chunk(
+    commands: &mut Commands,
+    chunk_map: &mut ChunkMap,
+    coord: IVec3,
+    lod: u32,
+    config: &TerrainGeneratorConfig,
 ) {
-    info!("Applying ground function");
-    for (entity, mut chunk) in new_chunks.iter_mut() {
-        debug!(
-            "Applying ground function to chunk '{entity:?}' at {}",
-            chunk.position
-        );
-        let ground_function = |pos: Vec3| -> f32 { pos.y };
+    let chunk = TerrainChunk::with_lod(coord, config.chunk_size, config.cube_edge_length, lod);
+    let entity = commands
+        .spawn((chunk, ChunkRebuild { resample: true }))
+        .id();
+    chunk_map.0.insert(coord, entity);
+}
+
+fn viewer_focuses(viewers: &Query<(&TerrainViewer, &GlobalTransform)>) -> Vec<Vec3> {
+    viewers
+        .iter()
+        .map(|(viewer, transform)| viewer.focus.unwrap_or(transform.translation()))
+        .collect()
+}
+
+/// Level of detail of the chunk at `coord` based on its distance to the closest viewer
+fn chunk_lod(coord: IVec3, focuses: &[Vec3], config: &TerrainGeneratorConfig) -> u32 {
+    if config.lod_levels == 0 {
+        return 0;
+    }
+
+    let chunk_extent = config.chunk_size.as_vec3() * config.cube_edge_length;
+    let center = coord.as_vec3() + Vec3::splat(0.5f32);
+    let Some(distance) = focuses
+        .iter()
+        .map(|focus| center.distance(*focus / chunk_extent))
+        .reduce(f32::min)
+    else {
+        return 0;
+    };
+
+    let lod_distance = config.lod_distance.max(f32::EPSILON);
+    if distance < lod_distance {
+        0
+    } else {
+        ((distance / lod_distance).log2() as u32 + 1).min(config.lod_levels)
+    }
+}
 
-        for point in chunk.points.iter_mut() {
-            point.value = ground_function(point.position);
+/// Spawn chunks within view radius of the viewers, closest first,
+/// and despawn the ones that went out of range. Edited chunks are kept aside
+/// and come back with their points, so the edits and their history still apply
+pub(super) fn stream_chunks(
+    mut commands: Commands,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut stored_chunks: ResMut<StoredChunks>,
+    chunks: Query<(&TerrainChunk, Option<&ChunkRebuild>, Option<&ChunkTask>)>,
+    viewers: Query<(&TerrainViewer, &GlobalTransform)>,
+    config: Res<TerrainGeneratorConfig>,
+) {
+    let chunk_extent = config.chunk_size.as_vec3() * config.cube_edge_length;
+    let radius = config.view_radius.min(MAX_VIEW_RADIUS) as i32;
+    let radius_squared = radius as i64 * radius as i64;
+    let focuses = viewer_focuses(&viewers);
+    let centers: Vec<IVec3> = focuses
+        .iter()
+        .map(|focus| (*focus / chunk_extent).floor().as_ivec3())
+        .collect();
+    let in_range = |coord: IVec3| {
+        centers.iter().any(|center| {
+            // Far away chunks can overflow `i32` once squared
+            let offset = (coord - *center).as_i64vec3();
+            offset.dot(offset) <= radius_squared
+        })
+    };
+
+    chunk_map.0.retain(|coord, entity| {
+        let keep = in_range(*coord);
+        if !keep {
+            debug!("Despawning chunk '{entity:?}' at {coord}");
+            if let Ok((chunk, rebuild, task)) = chunks.get(*entity) {
+                if let Some(edits) = chunk.edits(!is_resampling(rebuild, task)) {
+                    stored_chunks.0.insert(*coord, edits);
+                }
+            }
+            commands.entity(*entity).despawn();
         }
+        keep
+    });
+
+    let mut missing = HashMap::new();
+    for center in &centers {
+        for z in -radius..=radius {
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    let offset = IVec3::new(x, y, z);
+                    let distance = offset.dot(offset);
+                    let coord = *center + offset;
+                    if distance > radius * radius || chunk_map.0.contains_key(&coord) {
+                        continue;
+                    }
+                    let closest = missing.entry(coord).or_insert(distance);
+                    *closest = distance.min(*closest);
+                }
+            }
+        }
+    }
+
+    let mut missing: Vec<_> = missing.into_iter().collect();
+    missing.sort_unstable_by_key(|&(coord, distance)| (distance, coord.to_array()));
+    for (coord, _) in missing
+        .into_iter()
+        .take(config.chunks_per_frame.max(1) as usize)
+    {
+        let lod = chunk_lod(coord, &focuses, &config);
+        let Some(mut chunk) = stored_chunks.0.remove(&coord) else {
+            spawn_chunk(&mut commands, &mut chunk_map, coord, lod, &config);
+            continue;
+        };
+        debug!("Respawning edited chunk at {coord}");
+        let resample = chunk.set_lod(lod, config.chunk_size, config.cube_edge_length);
+        let entity = commands.spawn((chunk, ChunkRebuild { resample })).id();
+        chunk_map.0.insert(coord, entity);
     }
 }
 
-pub(super) fn generate_chunks(
+/// Rebuild chunks whose level of detail changed since the viewers moved,
+/// the old mesh stays visible until the new one is ready.
+/// Edited and loaded points are kept and put back once the chunk returns to their level
+pub(super) fn update_chunk_lods(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    changed_chunks: Query<(Entity, &TerrainChunk), Changed<TerrainChunk>>,
+    chunks: Query<(Entity, &TerrainChunk)>,
+    viewers: Query<(&TerrainViewer, &GlobalTransform)>,
     config: Res<TerrainGeneratorConfig>,
 ) {
-    info!("Generating meshes");
-    for (entity, chunk) in changed_chunks.iter() {
+    let focuses = viewer_focuses(&viewers);
+    for (entity, chunk) in chunks.iter() {
+        let lod = chunk_lod(chunk.coord, &focuses, &config);
+        if lod == chunk.lod {
+            continue;
+        }
+
         debug!(
-            "Generating mesh for chunk '{entity:?}' at {}",
-            chunk.position
+            "Changing level of detail of chunk '{entity:?}' from {} to {lod}",
+            chunk.lod
         );
+        let mut chunk = chunk.clone();
+        let resample = chunk.set_lod(lod, config.chunk_size, config.cube_edge_length);
+        // Results of a task started for the old level of detail don't fit the new chunk
+        commands
+            .entity(entity)
+            .remove::<ChunkTask>()
+            .insert((chunk, ChunkRebuild { resample }));
+    }
+}
 
-        // Go throught all of the points except for the final in each dimension
-        // This way we get only 0th point of every cube in chunk
-        let mut vertices = vec![];
-        let mut indices = vec![];
-        for z in 0..chunk.size.z {
-            for y in 0..chunk.size.y {
-                for x in 0..chunk.size.x {
-                    let points = &chunk.points;
-                    let point_size = chunk.point_size;
-                    #[rustfmt::skip]
-                    let cube = [
-                        // Bottom
-                        points[from_3D_to_1D_index(UVec3::new(x, y, z), point_size) as usize],
-                        points[from_3D_to_1D_index(UVec3::new(x + 1, y, z), point_size) as usize],
-                        points[from_3D_to_1D_index(UVec3::new(x + 1, y, z + 1), point_size) as usize],
-                        points[from_3D_to_1D_index(UVec3::new(x, y, z + 1), point_size) as usize],
-                        // Top
-                        points[from_3D_to_1D_index(UVec3::new(x, y + 1, z), point_size) as usize],
-                        points[from_3D_to_1D_index(UVec3::new(x + 1, y + 1, z), point_size) as usize],
-                        points[from_3D_to_1D_index(UVec3::new(x + 1, y + 1, z + 1), point_size) as usize],
-                        points[from_3D_to_1D_index(UVec3::new(x, y + 1, z + 1), point_size) as usize],
-                    ];
-
-                    // Compute cube configuration index by setting bits of the points that are below
-                    // the isosurface to 1
-                    let mut cube_index = 0;
-                    for (i, point) in cube.iter().enumerate() {
-                        if point.value < config.isolevel {
-                            cube_index |= 1 << i;
-                        }
-                    }
+/// Apply the brushes to the chunks they touch and re-mesh them without resampling,
+/// a frame without edits ends the brush stroke in the history
+pub(super) fn apply_terrain_edits(
+    mut commands: Commands,
+    mut edits: EventReader<TerrainEdit>,
+    mut chunks: Query<(
+        Entity,
+        &mut TerrainChunk,
+        Option<&ChunkRebuild>,
+        Option<&ChunkTask>,
+    )>,
+    chunk_map: Res<ChunkMap>,
+    mut history: ResMut<EditHistory>,
+    config: Res<TerrainGeneratorConfig>,
+) {
+    let edits: Vec<_> = edits.iter().copied().collect();
+    if edits.is_empty() {
+        history.end_stroke(config.history_size);
+        return;
+    }
 
-                    // Get intersecred edges for the cube configuration,
-                    // calculate points along them
-                    let intersected_edges = INTERSECTED_EDGES[cube_index];
-                    for edge in intersected_edges {
-                        if edge == -1 {
-                            break;
-                        }
-                        let (p1_idx, p2_idx) = EDGE_VERTICES[edge as usize];
-                        let vertex = utils::vertex_lerp(
-                            config.isolevel,
-                            cube[p1_idx as usize],
-                            cube[p2_idx as usize],
-                        );
-                        if let Some(idx) = vertices.iter().position(|el| *el == vertex) {
-                            indices.push(idx as u16);
-                        } else {
-                            vertices.push(vertex);
-                            indices.push((vertices.len() - 1) as u16);
-                        }
-                    }
+    let mut touched_chunks = Vec::new();
+    for (entity, mut chunk, rebuild, task) in chunks.iter_mut() {
+        // Edits of chunks waiting for new points would be overwritten
+        if is_resampling(rebuild, task) {
+            continue;
+        }
+
+        let mut touched = false;
+        for edit in &edits {
+            touched |= history.apply_edit(&mut chunk, edit);
+        }
+        if touched {
+            touched_chunks.push((entity, chunk.coord));
+        }
+    }
+
+    // Smoothing leaves the outermost points to the neighbours that have all of their neighbours
+    if edits.iter().any(|edit| edit.mode == BrushMode::Smooth) {
+        for (entity, coord) in touched_chunks.clone() {
+            for neighbour_coord in neighbour_coords(coord) {
+                let Some(&neighbour) = chunk_map.0.get(&neighbour_coord) else {
+                    continue;
+                };
+                let Ok([(_, mut chunk, ..), (_, neighbour_chunk, rebuild, task)]) =
+                    chunks.get_many_mut([entity, neighbour])
+                else {
+                    continue;
+                };
+                if !is_resampling(rebuild, task)
+                    && history.copy_shared_points(&mut chunk, &neighbour_chunk)
+                {
+                    touched_chunks.push((entity, coord));
                 }
             }
         }
+    }
 
-        // Compute normals
-        let mut normals = vec![Vec3::ZERO; vertices.len()];
-        for chunk in indices.chunks_exact(3) {
-            let idx_a = chunk[0] as usize;
-            let idx_b = chunk[1] as usize;
-            let idx_c = chunk[2] as usize;
+    for (entity, _) in touched_chunks {
+        debug!("Re-meshing edited chunk '{entity:?}'");
+        commands
+            .entity(entity)
+            .insert(ChunkRebuild { resample: false });
+    }
+}
 
-            let vertex_a = vertices[idx_a];
-            let vertex_b = vertices[idx_b];
-            let vertex_c = vertices[idx_c];
+/// Coordinates of the 26 chunks around `coord`
+fn neighbour_coords(coord: IVec3) -> impl Iterator<Item = IVec3> {
+    (-1..=1)
+        .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+        .filter(|offset| *offset != IVec3::ZERO)
+        .map(move |offset| coord + offset)
+}
 
-            let edge_ab = vertex_b - vertex_a;
-            let edge_ac = vertex_c - vertex_a;
+/// Whether the chunk's points are about to be replaced with newly sampled ones
+fn is_resampling(rebuild: Option<&ChunkRebuild>, task: Option<&ChunkTask>) -> bool {
+    rebuild.is_some_and(|rebuild| rebuild.resample) || task.is_some_and(|task| task.resample)
+}
 
-            let wheighted_normal = edge_ab.cross(edge_ac);
+/// Revert or reapply the latest strokes on the chunks still holding the same points
+pub(super) fn apply_history_commands(
+    mut commands: Commands,
+    mut history_commands: EventReader<HistoryCommand>,
+    mut history: ResMut<EditHistory>,
+    chunk_map: Res<ChunkMap>,
+    mut stored_chunks: ResMut<StoredChunks>,
+    mut chunks: Query<(&mut TerrainChunk, Option<&ChunkRebuild>, Option<&ChunkTask>)>,
+    config: Res<TerrainGeneratorConfig>,
+) {
+    for command in history_commands.iter() {
+        let step = match command {
+            HistoryCommand::Undo => history.undo(config.history_size),
+            HistoryCommand::Redo => history.redo(config.history_size),
+        };
+        let Some(step) = step else {
+            debug!("Nothing to {command:?}");
+            continue;
+        };
 
-            normals[idx_a] += wheighted_normal;
-            normals[idx_b] += wheighted_normal;
-            normals[idx_c] += wheighted_normal;
+        info!("{command:?} of {} points", step.len());
+        for coord in step.coords() {
+            let Some(&entity) = chunk_map.0.get(&coord) else {
+                // Despawned chunks are meshed once they come back
+                if let Some(chunk) = stored_chunks.0.get_mut(&coord) {
+                    match command {
+                        HistoryCommand::Undo => step.revert(chunk),
+                        HistoryCommand::Redo => step.reapply(chunk),
+                    };
+                }
+                continue;
+            };
+            let Ok((mut chunk, rebuild, task)) = chunks.get_mut(entity) else {
+                continue;
+            };
+            // Resampled points don't have the values the step was recorded on
+            if is_resampling(rebuild, task) {
+                continue;
+            }
+            let changed = match command {
+                HistoryCommand::Undo => step.revert(&mut chunk),
+                HistoryCommand::Redo => step.reapply(&mut chunk),
+            };
+            if changed {
+                commands
+                    .entity(entity)
+                    .insert(ChunkRebuild { resample: false });
+            }
         }
+    }
+}
 
-        for n in normals.iter_mut() {
-            *n = n.normalize();
+/// Replace the chunks with the saved ones, they are meshed with their saved points
+pub(super) fn load_world_file(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadWorldEvent>,
+    existing_chunks: Query<Entity, With<TerrainChunk>>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut stored_chunks: ResMut<StoredChunks>,
+    mut history: ResMut<EditHistory>,
+    mut config: ResMut<TerrainGeneratorConfig>,
+) {
+    let Some(event) = load_events.iter().last() else {
+        return;
+    };
+    let world = match load_world(&event.path) {
+        Ok(world) => world,
+        Err(err) => {
+            error!("Failed to load world from {}: {err}", event.path.display());
+            return;
         }
+    };
 
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_indices(Some(Indices::U16(indices)));
+    info!(
+        "Loading {} chunks from {}",
+        world.chunks.len(),
+        event.path.display()
+    );
+    for entity in existing_chunks.iter() {
+        commands.entity(entity).despawn();
+    }
+    chunk_map.0.clear();
+    stored_chunks.0.clear();
+    history.clear();
+    config.take_setup(world.config);
+    for chunk in world.chunks {
+        let coord = chunk.coord;
+        let entity = commands
+            .spawn((chunk, ChunkRebuild { resample: false }))
+            .id();
+        chunk_map.0.insert(coord, entity);
+    }
+}
 
-        debug!("Inserting mesh into `{entity:?}`");
-        commands.entity(entity).insert(PbrBundle {
-            mesh: meshes.add(mesh),
-            material: materials.add(StandardMaterial {
-                base_color: Color::rgb(0.3, 0.5, 0.3),
-                double_sided: true,
-                // cull_mode: None,
-                perceptual_roughness: 1f32,
-                metallic: 0f32,
-                reflectance: 0f32,
-                ..Default::default()
-            }),
-            ..Default::default()
+/// Write the sampled chunks and the edited ones out of view into a world file,
+/// chunks still waiting for points are left out
+pub(super) fn save_world_file(
+    mut save_events: EventReader<SaveWorldEvent>,
+    chunks: Query<(&TerrainChunk, Option<&ChunkRebuild>, Option<&ChunkTask>)>,
+    stored_chunks: Res<StoredChunks>,
+    config: Res<TerrainGeneratorConfig>,
+) {
+    let sampled_chunks: Vec<_> = chunks
+        .iter()
+        .filter(|(_, rebuild, task)| !is_resampling(*rebuild, *task))
+        .map(|(chunk, _, _)| chunk)
+        .chain(stored_chunks.0.values())
+        .collect();
+    for event in save_events.iter() {
+        info!(
+            "Saving {} chunks to {}",
+            sampled_chunks.len(),
+            event.path.display()
+        );
+        if let Err(err) = save_world(&event.path, &config, sampled_chunks.iter().copied()) {
+            error!("Failed to save world to {}: {err}", event.path.display());
+        }
+    }
+}
+
+/// Write the meshes of the chunks into files, chunks without a mesh yet are left out
+pub(super) fn export_chunk_meshes(
+    mut export_events: EventReader<ExportTerrainEvent>,
+    chunks: Query<(&TerrainChunk, &GlobalTransform, &Handle<Mesh>)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    let chunk_meshes: Vec<_> = chunks
+        .iter()
+        .filter_map(|(chunk, transform, handle)| {
+            Some(ChunkMesh {
+                coord: chunk.coord,
+                transform: transform.compute_transform(),
+                mesh: meshes.get(handle)?,
+            })
+        })
+        .collect();
+    for event in export_events.iter() {
+        match export_terrain(&event.path, &chunk_meshes, event.options) {
+            Ok(files) => info!("Exported {} chunks to {files:?}", chunk_meshes.len()),
+            Err(err) => error!(
+                "Failed to export terrain to {}: {err}",
+                event.path.display()
+            ),
+        }
+    }
+}
+
+/// Sample and mesh chunks on the async compute pool, one task per chunk
+pub(super) fn spawn_chunk_tasks(
+    mut commands: Commands,
+    pending_chunks: Query<(Entity, &TerrainChunk, &ChunkRebuild), Without<ChunkTask>>,
+    config: Res<TerrainGeneratorConfig>,
+    density: Res<TerrainDensity>,
+) {
+    if pending_chunks.is_empty() {
+        return;
+    }
+
+    info!("Spawning chunk tasks");
+    let thread_pool = AsyncComputeTaskPool::get();
+    let config = Arc::new(config.clone());
+    for (entity, chunk, rebuild) in pending_chunks.iter() {
+        debug!(
+            "Spawning {rebuild:?} task for chunk '{entity:?}' at {}",
+            chunk.position
+        );
+        let mut chunk = chunk.clone();
+        let rebuild = *rebuild;
+        let config = config.clone();
+        let density = density.0.clone();
+        let task = thread_pool.spawn(async move {
+            let points = rebuild.resample.then(|| {
+                chunk.sample(density.as_ref(), &config.materials);
+                chunk.points.clone()
+            });
+            // Points that weren't resampled may not match the density function anymore
+            let density = rebuild.resample.then_some(density.as_ref());
+            let mesh = mesh_chunk(&chunk, &config, density);
+            ChunkTaskResult {
+                points,
+                mesh,
+                splatting: config.splatting.enabled,
+            }
         });
+        commands
+            .entity(entity)
+            .remove::<ChunkRebuild>()
+            .insert(ChunkTask {
+                task,
+                resample: rebuild.resample,
+            });
+    }
+}
+
+/// Insert finished meshes, at most `chunks_per_frame` of them every frame
+/// so that a big world streaming in doesn't freeze the app
+pub(super) fn apply_chunk_tasks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material: Res<TerrainMaterial>,
+    mut chunks: Query<(Entity, &mut TerrainChunk, &mut ChunkTask)>,
+    config: Res<TerrainGeneratorConfig>,
+) {
+    let mut budget = config.chunks_per_frame.max(1);
+    for (entity, mut chunk, mut task) in chunks.iter_mut() {
+        if budget == 0 {
+            break;
+        }
+        let Some(result) = future::block_on(future::poll_once(&mut task.task)) else {
+            continue;
+        };
+        budget -= 1;
+
+        if let Some(points) = result.points {
+            chunk.points = points;
+        }
+
+        debug!("Inserting mesh into `{entity:?}`");
+        let mut entity = commands.entity(entity);
+        entity.remove::<ChunkTask>();
+        // Splatting may have been toggled since the chunk was last meshed,
+        // or while this mesh was being built
+        if result.splatting {
+            entity
+                .remove::<Handle<StandardMaterial>>()
+                .insert(MaterialMeshBundle {
+                    mesh: meshes.add(result.mesh),
+                    material: material.triplanar.clone(),
+                    ..Default::default()
+                });
+        } else {
+            entity
+                .remove::<Handle<TriplanarMaterial>>()
+                .insert(PbrBundle {
+                    mesh: meshes.add(result.mesh),
+                    material: material.standard.clone(),
+                    ..Default::default()
+                });
+        }
     }
 }
 
 #[rustfmt::skip]
 pub(super) fn draw_bounding_box(mut gizmos: Gizmos, config: Res<TerrainGeneratorConfig>) {
-    if !config.show_gizmos {
+    if !config.show_gizmos || config.streaming {
         return;
     }
 
@@ -222,6 +607,25 @@ pub(super) fn draw_bounding_box(mut gizmos: Gizmos, config: Res<TerrainGenerator
     gizmos.line(Vec3::new(size_x, size_y, 0f32), Vec3::new(0f32, size_y, 0f32), Color::BLACK);
 }
 
+/// Outline every chunk, colored by its level of detail
+pub(super) fn draw_chunk_lods(
+    mut gizmos: Gizmos,
+    config: Res<TerrainGeneratorConfig>,
+    chunks: Query<&TerrainChunk>,
+) {
+    if !config.show_gizmos || config.lod_levels == 0 {
+        return;
+    }
+
+    let colors = [Color::GREEN, Color::YELLOW, Color::ORANGE, Color::RED];
+    for chunk in chunks.iter() {
+        let (min, max) = chunk.bounds();
+        let transform = Transform::from_translation((min + max) / 2f32).with_scale(max - min);
+        let color = colors[(chunk.lod as usize).min(colors.len() - 1)];
+        gizmos.cuboid(transform, color);
+    }
+}
+
 pub(super) fn draw_mesh_normals(
     mut gizmos: Gizmos,
     config: Res<TerrainGeneratorConfig>,
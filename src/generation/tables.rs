@@ -1,3 +1,29 @@
+use bevy::prelude::UVec3;
+
+/// Offsets of cube vertices from its 0th vertex
+pub const CUBE_CORNERS: [UVec3; 8] = [
+    // Bottom
+    UVec3::new(0, 0, 0),
+    UVec3::new(1, 0, 0),
+    UVec3::new(1, 0, 1),
+    UVec3::new(0, 0, 1),
+    // Top
+    UVec3::new(0, 1, 0),
+    UVec3::new(1, 1, 0),
+    UVec3::new(1, 1, 1),
+    UVec3::new(0, 1, 1),
+];
+
+/// Cube vertices of every face, counter clockwise when looking at the cube from outside
+pub const CUBE_FACES: [[u8; 4]; 6] = [
+    [0, 1, 2, 3],
+    [4, 7, 6, 5],
+    [0, 3, 7, 4],
+    [1, 5, 6, 2],
+    [0, 4, 5, 1],
+    [3, 2, 6, 7],
+];
+
 #[rustfmt::skip]
 /// A pair of vertices corresponding to an edge
 pub const EDGE_VERTICES: [(u8, u8); 12] = [
@@ -272,3 +298,46 @@ pub const INTERSECTED_EDGES: [[i8; 16]; 256] = [
     [0, 3, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
     [-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
 ];
+
+#[rustfmt::skip]
+/// Cube split into six tetrahedra around its diagonal from the 0th to the 6th vertex,
+/// given as cube vertices, every tetrahedron is positively oriented.
+/// Neighbouring cubes split their shared faces along the same diagonal
+pub const CUBE_TETRAHEDRA: [[u8; 4]; 6] = [
+    [0, 1, 5, 6],
+    [0, 2, 1, 6],
+    [0, 5, 4, 6],
+    [0, 4, 7, 6],
+    [0, 3, 2, 6],
+    [0, 7, 3, 6],
+];
+
+#[rustfmt::skip]
+/// A pair of tetrahedron vertices corresponding to an edge
+pub const TETRAHEDRON_EDGE_VERTICES: [(u8, u8); 6] = [
+    (0, 1), (1, 2), (2, 0), (0, 3), (1, 3), (2, 3),
+];
+
+#[rustfmt::skip]
+/// Edges that are intersected in a tetrahedron configuration
+/// edges are given in the triangulation order
+/// tetrahedron configuration is an index in the array
+/// `-1` means "not intersected"
+pub const TETRAHEDRON_INTERSECTED_EDGES: [[i8; 7]; 16] = [
+    [-1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 3, -1, -1, -1, -1],
+    [0, 4, 1, -1, -1, -1, -1],
+    [2, 3, 4, 2, 4, 1, -1],
+    [1, 5, 2, -1, -1, -1, -1],
+    [0, 5, 3, 0, 1, 5, -1],
+    [0, 4, 5, 0, 5, 2, -1],
+    [3, 4, 5, -1, -1, -1, -1],
+    [3, 5, 4, -1, -1, -1, -1],
+    [0, 2, 5, 0, 5, 4, -1],
+    [0, 5, 1, 0, 3, 5, -1],
+    [1, 2, 5, -1, -1, -1, -1],
+    [2, 1, 4, 2, 4, 3, -1],
+    [0, 1, 4, -1, -1, -1, -1],
+    [0, 3, 2, -1, -1, -1, -1],
+    [-1, -1, -1, -1, -1, -1, -1],
+];
@@ -0,0 +1,240 @@
+//! Triplanar texture splatting, meshes have no UVs so textures are projected along the world axes
+//! and blended by the direction of the normal, up to four layers are blended by per vertex weights
+
+use bevy::{
+    asset::load_internal_asset,
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::{
+        mesh::{MeshVertexAttribute, MeshVertexBufferLayout},
+        render_resource::{
+            AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+            VertexFormat,
+        },
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use super::MaterialId;
+
+/// Maximum amount of splat layers, their weights are packed into a single vector
+pub const MAX_SPLAT_LAYERS: usize = 4;
+
+/// How much each of the projections along the X, Y and Z axes contributes to the vertex
+pub const ATTRIBUTE_TRIPLANAR_WEIGHTS: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_TriplanarWeights",
+    1_170_468_212,
+    VertexFormat::Float32x3,
+);
+
+/// Weights of the splat layers, vertices covered by no layer have all of them zero
+pub const ATTRIBUTE_SPLAT_WEIGHTS: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_SplatWeights",
+    1_170_468_213,
+    VertexFormat::Float32x4,
+);
+
+const TRIPLANAR_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5d1c_7a4e_93b2_0f61);
+
+pub struct TriplanarPlugin;
+
+impl Plugin for TriplanarPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            TRIPLANAR_SHADER_HANDLE,
+            "triplanar.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<TriplanarMaterial>::default());
+    }
+}
+
+/// Textures used instead of the vertex colors when splatting is enabled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SplatSettings {
+    /// Use [`TriplanarMaterial`] instead of a [`StandardMaterial`] tinted by vertex colors
+    pub enabled: bool,
+    /// Texture repeats per world unit
+    pub texture_scale: f32,
+    /// Higher values narrow the blend between the projections
+    pub blend_sharpness: f32,
+    /// Only the first [`MAX_SPLAT_LAYERS`] layers are used
+    pub layers: Vec<SplatLayer>,
+}
+
+impl Default for SplatSettings {
+    fn default() -> Self {
+        let layer = |materials: &[MaterialId], tint: Color| SplatLayer {
+            tint,
+            materials: materials.to_vec(),
+            ..Default::default()
+        };
+        Self {
+            enabled: false,
+            texture_scale: 0.25f32,
+            blend_sharpness: 4f32,
+            layers: vec![
+                layer(
+                    &[MaterialId::Rock, MaterialId::Dirt],
+                    MaterialId::Rock.color(),
+                ),
+                layer(&[MaterialId::Grass], MaterialId::Grass.color()),
+                layer(&[MaterialId::Sand], MaterialId::Sand.color()),
+                layer(&[MaterialId::Snow], MaterialId::Snow.color()),
+            ],
+        }
+    }
+}
+
+/// Texture covering the vertices that match all of the layer's conditions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SplatLayer {
+    /// Asset path of the texture, the layer is drawn with a flat tint without one
+    pub texture: Option<String>,
+    pub tint: Color,
+    /// Materials covered by the layer, empty covers all of them
+    pub materials: Vec<MaterialId>,
+    /// Range of the angle between the surface and the horizontal plane in degrees
+    pub slope: Vec2,
+    /// Range of the height in the world, `None` covers every height
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<Vec2>,
+}
+
+impl Default for SplatLayer {
+    /// White layer covering everything
+    fn default() -> Self {
+        Self {
+            texture: None,
+            tint: Color::WHITE,
+            materials: Vec::new(),
+            slope: Vec2::new(0f32, 180f32),
+            height: None,
+        }
+    }
+}
+
+impl SplatLayer {
+    fn covers(&self, material: MaterialId, slope: f32, height: f32) -> bool {
+        (self.materials.is_empty() || self.materials.contains(&material))
+            && (self.slope.x..=self.slope.y).contains(&slope)
+            && self
+                .height
+                .is_none_or(|range| (range.x..=range.y).contains(&height))
+    }
+}
+
+impl SplatSettings {
+    /// Weights of the projections along the axes for a vertex with the given normal
+    pub fn triplanar_weights(&self, normal: Vec3) -> [f32; 3] {
+        let weights = normal.abs().powf(self.blend_sharpness);
+        let sum = weights.x + weights.y + weights.z;
+        if sum > 0f32 {
+            (weights / sum).to_array()
+        } else {
+            [0f32, 1f32, 0f32]
+        }
+    }
+
+    /// Weights of the layers covering the vertex, every covering layer gets the same weight
+    pub fn splat_weights(&self, position: Vec3, normal: Vec3, material: MaterialId) -> [f32; 4] {
+        let slope = normal.y.clamp(-1f32, 1f32).acos().to_degrees();
+        let mut weights = [0f32; MAX_SPLAT_LAYERS];
+        for (weight, layer) in weights.iter_mut().zip(&self.layers) {
+            if layer.covers(material, slope, position.y) {
+                *weight = 1f32;
+            }
+        }
+        let sum: f32 = weights.iter().sum();
+        if sum > 0f32 {
+            weights.map(|weight| weight / sum)
+        } else {
+            weights
+        }
+    }
+}
+
+/// Projects the splat layers along the world axes and lights them like a rough
+/// [`StandardMaterial`], vertices not covered by any layer keep their vertex colors
+#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone, Default)]
+#[uuid = "3c0f1e9a-6b57-4a3e-9d2c-81f4b7e5a260"]
+pub struct TriplanarMaterial {
+    /// Linear colors multiplied with the layer textures
+    #[uniform(0)]
+    pub tints: [Vec4; MAX_SPLAT_LAYERS],
+    #[uniform(0)]
+    pub texture_scale: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub layer_0: Option<Handle<Image>>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub layer_1: Option<Handle<Image>>,
+    #[texture(5)]
+    #[sampler(6)]
+    pub layer_2: Option<Handle<Image>>,
+    #[texture(7)]
+    #[sampler(8)]
+    pub layer_3: Option<Handle<Image>>,
+}
+
+impl TriplanarMaterial {
+    /// Material drawing the layers of `settings`, textures are loaded with `asset_server`
+    pub fn new(settings: &SplatSettings, asset_server: &AssetServer) -> Self {
+        let mut tints = [Vec4::ONE; MAX_SPLAT_LAYERS];
+        let mut textures: [Option<Handle<Image>>; MAX_SPLAT_LAYERS] = default();
+        for ((tint, texture), layer) in tints.iter_mut().zip(&mut textures).zip(&settings.layers) {
+            *tint = Vec4::from(layer.tint.as_linear_rgba_f32());
+            *texture = layer
+                .texture
+                .as_ref()
+                .filter(|path| !path.is_empty())
+                .map(|path| asset_server.load(path.as_str()));
+        }
+        let [layer_0, layer_1, layer_2, layer_3] = textures;
+        Self {
+            tints,
+            texture_scale: settings.texture_scale,
+            layer_0,
+            layer_1,
+            layer_2,
+            layer_3,
+        }
+    }
+}
+
+impl Material for TriplanarMaterial {
+    fn vertex_shader() -> ShaderRef {
+        TRIPLANAR_SHADER_HANDLE.typed().into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        TRIPLANAR_SHADER_HANDLE.typed().into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Prepasses keep the vertex layout of their own shaders
+        if descriptor.vertex.shader != TRIPLANAR_SHADER_HANDLE.typed::<Shader>() {
+            return Ok(());
+        }
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            Mesh::ATTRIBUTE_COLOR.at_shader_location(4),
+            ATTRIBUTE_TRIPLANAR_WEIGHTS.at_shader_location(7),
+            ATTRIBUTE_SPLAT_WEIGHTS.at_shader_location(8),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+#import bevy_pbr::mesh_bindings           mesh
+#import bevy_pbr::mesh_functions          as mesh_functions
+#import bevy_pbr::mesh_view_bindings      view
+#import bevy_pbr::pbr_functions           as pbr_functions
+#import bevy_core_pipeline::tonemapping   tone_mapping
+
+struct TriplanarParams {
+    tints: array<vec4<f32>, 4>,
+    texture_scale: f32,
+};
+
+@group(1) @binding(0) var<uniform> params: TriplanarParams;
+@group(1) @binding(1) var layer_0_texture: texture_2d<f32>;
+@group(1) @binding(2) var layer_0_sampler: sampler;
+@group(1) @binding(3) var layer_1_texture: texture_2d<f32>;
+@group(1) @binding(4) var layer_1_sampler: sampler;
+@group(1) @binding(5) var layer_2_texture: texture_2d<f32>;
+@group(1) @binding(6) var layer_2_sampler: sampler;
+@group(1) @binding(7) var layer_3_texture: texture_2d<f32>;
+@group(1) @binding(8) var layer_3_sampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(4) color: vec4<f32>,
+    @location(7) triplanar_weights: vec3<f32>,
+    @location(8) splat_weights: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) triplanar_weights: vec3<f32>,
+    @location(4) splat_weights: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.world_position = mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(vertex.position, 1.0));
+    out.clip_position = mesh_functions::mesh_position_world_to_clip(out.world_position);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(vertex.normal);
+    out.color = vertex.color;
+    out.triplanar_weights = vertex.triplanar_weights;
+    out.splat_weights = vertex.splat_weights;
+    return out;
+}
+
+// Texture projected along the X, Y and Z axes, the projections are blended by `weights`.
+// Wrapped coordinates would break the derivatives, so they are taken before wrapping
+fn triplanar(
+    layer_texture: texture_2d<f32>,
+    layer_sampler: sampler,
+    position: vec3<f32>,
+    weights: vec3<f32>,
+) -> vec4<f32> {
+    let uv_x = position.zy;
+    let uv_y = position.xz;
+    let uv_z = position.xy;
+    let x = textureSampleGrad(layer_texture, layer_sampler, fract(uv_x), dpdx(uv_x), dpdy(uv_x));
+    let y = textureSampleGrad(layer_texture, layer_sampler, fract(uv_y), dpdx(uv_y), dpdy(uv_y));
+    let z = textureSampleGrad(layer_texture, layer_sampler, fract(uv_z), dpdx(uv_z), dpdy(uv_z));
+    return x * weights.x + y * weights.y + z * weights.z;
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> @location(0) vec4<f32> {
+    let position = in.world_position.xyz * params.texture_scale;
+    let weights = in.triplanar_weights / max(dot(in.triplanar_weights, vec3(1.0)), 0.0001);
+    let splat = in.splat_weights;
+
+    // Whatever the layers don't cover shows the vertex color
+    var color = in.color * (1.0 - min(dot(splat, vec4(1.0)), 1.0));
+    color += splat.x * params.tints[0] * triplanar(layer_0_texture, layer_0_sampler, position, weights);
+    color += splat.y * params.tints[1] * triplanar(layer_1_texture, layer_1_sampler, position, weights);
+    color += splat.z * params.tints[2] * triplanar(layer_2_texture, layer_2_sampler, position, weights);
+    color += splat.w * params.tints[3] * triplanar(layer_3_texture, layer_3_sampler, position, weights);
+
+    var pbr_input = pbr_functions::pbr_input_new();
+    pbr_input.material.base_color = vec4(color.rgb, 1.0);
+    pbr_input.material.perceptual_roughness = 1.0;
+    pbr_input.material.metallic = 0.0;
+    pbr_input.material.reflectance = 0.0;
+    pbr_input.frag_coord = in.clip_position;
+    pbr_input.world_position = in.world_position;
+    pbr_input.world_normal = pbr_functions::prepare_world_normal(in.world_normal, true, is_front);
+    pbr_input.N = normalize(pbr_input.world_normal);
+    pbr_input.is_orthographic = view.projection[3].w == 1.0;
+    pbr_input.V = pbr_functions::calculate_view(in.world_position, pbr_input.is_orthographic);
+    pbr_input.flags = mesh.flags;
+
+    var output_color = pbr_functions::pbr(pbr_input);
+#ifdef TONEMAP_IN_SHADER
+    output_color = tone_mapping(output_color, view.color_grading);
+#endif
+    return output_color;
+}
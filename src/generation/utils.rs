@@ -1,7 +1,9 @@
 use bevy::prelude::{UVec3, Vec3};
 
+use super::MaterialId;
+
 #[allow(non_snake_case)]
-pub(super) fn _from_1D_to_3D_index(idx: u32, dimensions: UVec3) -> UVec3 {
+pub(super) fn from_1D_to_3D_index(idx: u32, dimensions: UVec3) -> UVec3 {
     let x = idx % dimensions.x;
     let y = (idx / dimensions.x) % dimensions.y;
     let z = idx / (dimensions.x * dimensions.y);
@@ -17,3 +19,12 @@ pub(super) fn vertex_lerp(isolevel: f32, p1: super::Point, p2: super::Point) ->
     let t = (isolevel - p1.value) / (p2.value - p1.value);
     p1.position + t * (p2.position - p1.position)
 }
+
+/// Material of the vertex on the edge between the points, the surface shows the solid one
+pub(super) fn vertex_material(isolevel: f32, p1: super::Point, p2: super::Point) -> MaterialId {
+    if p1.value < isolevel {
+        p1.material
+    } else {
+        p2.material
+    }
+}
@@ -3,6 +3,7 @@ use bevy_egui::EguiPlugin;
 use terrain_procgen::generation::*;
 
 mod camera;
+mod sculpt;
 mod ui;
 
 fn main() {
@@ -11,6 +12,7 @@ fn main() {
         .add_plugins(EguiPlugin)
         .add_plugins(MarchingCubesTerrain)
         .add_plugins(camera::CameraPlugin)
+        .add_plugins(sculpt::SculptPlugin)
         .add_event::<AppExit>()
         .add_systems(Update, bevy::window::close_on_esc)
         .add_systems(Update, ui::ui_system)
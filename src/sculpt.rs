@@ -0,0 +1,141 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+use terrain_procgen::generation::{
+    editing::raycast_terrain, BrushMode, ChunkMap, HistoryCommand, TerrainChunk, TerrainEdit,
+    TerrainGeneratorConfig,
+};
+
+pub struct SculptPlugin;
+
+impl Plugin for SculptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Brush>()
+            .init_resource::<BrushTarget>()
+            .add_systems(
+                Update,
+                (
+                    aim_brush,
+                    sculpt_with_mouse.after(aim_brush),
+                    undo_with_keys,
+                ),
+            );
+    }
+}
+
+/// Brush under the cursor, left click builds and shift + left click digs
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Brush {
+    pub mode: BrushMode,
+    pub radius: f32,
+    /// Strength of the edits per second of holding the button
+    pub strength: f32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            mode: BrushMode::default(),
+            radius: 2f32,
+            strength: 4f32,
+        }
+    }
+}
+
+/// Point of the terrain under the cursor
+#[derive(Resource, Debug, Default)]
+struct BrushTarget(Option<Vec3>);
+
+/// Farthest terrain the cursor can reach
+const MAX_REACH: f32 = 500f32;
+
+/// Cast a ray from the cursor into the sampled chunks
+fn aim_brush(
+    mut contexts: EguiContexts,
+    primary_window_query: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    chunk_map: Res<ChunkMap>,
+    chunks: Query<&TerrainChunk>,
+    config: Res<TerrainGeneratorConfig>,
+    mut target: ResMut<BrushTarget>,
+) {
+    target.0 = None;
+    let ctx = contexts.ctx_mut();
+    if ctx.wants_pointer_input() || ctx.is_pointer_over_area() {
+        return;
+    }
+    let Some(cursor) = primary_window_query.single().cursor_position() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    let chunk_extent = config.chunk_size.as_vec3() * config.cube_edge_length;
+    let value_at = |pos: Vec3| {
+        let coord = (pos / chunk_extent).floor().as_ivec3();
+        let entity = chunk_map.0.get(&coord)?;
+        chunks.get(*entity).ok()?.value_at(pos)
+    };
+    let step = config.cube_edge_length * 0.5f32;
+    target.0 = raycast_terrain(ray, MAX_REACH, step, config.isolevel, value_at);
+}
+
+fn sculpt_with_mouse(
+    target: Res<BrushTarget>,
+    brush: Res<Brush>,
+    input_mouse: Res<Input<MouseButton>>,
+    input_keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut edits: EventWriter<TerrainEdit>,
+    mut gizmos: Gizmos,
+) {
+    let Some(center) = target.0 else {
+        return;
+    };
+
+    let color = Color::rgba(1f32, 1f32, 1f32, 0.5f32);
+    match brush.mode {
+        BrushMode::Cube => gizmos.cuboid(
+            Transform::from_translation(center).with_scale(Vec3::splat(brush.radius * 2f32)),
+            color,
+        ),
+        _ => {
+            gizmos.sphere(center, Quat::IDENTITY, brush.radius, color);
+        }
+    }
+
+    if !input_mouse.pressed(MouseButton::Left) {
+        return;
+    }
+    let dig = input_keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let strength = brush.strength * time.delta_seconds();
+    edits.send(TerrainEdit {
+        center,
+        radius: brush.radius,
+        strength: if dig { -strength } else { strength },
+        mode: brush.mode,
+    });
+}
+
+/// Ctrl + Z undoes the latest stroke, Ctrl + Y or Ctrl + Shift + Z redoes it
+fn undo_with_keys(
+    mut contexts: EguiContexts,
+    input_keys: Res<Input<KeyCode>>,
+    mut history_commands: EventWriter<HistoryCommand>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if !input_keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let shift = input_keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if input_keys.just_pressed(KeyCode::Y) || (shift && input_keys.just_pressed(KeyCode::Z)) {
+        history_commands.send(HistoryCommand::Redo);
+    } else if input_keys.just_pressed(KeyCode::Z) {
+        history_commands.send(HistoryCommand::Undo);
+    }
+}
@@ -1,21 +1,81 @@
-use bevy::prelude::{EventWriter, Local, ResMut, UVec3};
+use std::{fmt::Debug, path::PathBuf};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::{Color, EventWriter, Local, Res, ResMut, UVec3, Vec2, Vec3},
+};
 use bevy_egui::{
-    egui::{DragValue, Grid, Slider, TopBottomPanel, Window},
+    egui::{Color32, ComboBox, DragValue, Grid, Slider, TextEdit, TopBottomPanel, Ui, Window},
     EguiContexts,
 };
-use terrain_procgen::generation::{GenerateTerrainEvent, TerrainGeneratorConfig};
+use terrain_procgen::generation::{
+    expression::{Expression, ExpressionError},
+    graph::DensityGraph,
+    noise::{FractalKind, NoiseDimensions, NoiseKind, NoiseSettings},
+    triplanar::MAX_SPLAT_LAYERS,
+    BrushMode, ColorMode, ColorRamp, DensitySettings, EditHistory, ExportFormat, ExportOptions,
+    ExportTerrainEvent, GenerateTerrainEvent, HistoryCommand, LoadWorldEvent, MaterialId,
+    MeshIndexFormat, MesherKind, NormalMode, Preset, SaveWorldEvent, SplatLayer,
+    TerrainGeneratorConfig, MAX_VIEW_RADIUS,
+};
 
-#[derive(Debug, Default)]
+use crate::sculpt::Brush;
+
+#[derive(Debug)]
 pub struct UIState {
     is_gen_window_expanded: bool,
+    /// Regenerate terrain every time settings change
+    live_update: bool,
+    /// File the config is saved to and loaded from, TOML for `.toml` files and RON otherwise
+    config_path: String,
+    /// Outcome of the last config save or load
+    config_status: Option<Result<String, String>>,
+    /// File the world is saved to and loaded from
+    world_path: String,
+    /// File the meshes are exported to, the extension follows the format
+    export_path: String,
+    export_options: ExportOptions,
+    /// Expression source that was parsed last and its error,
+    /// parsing again only when the text changes
+    expression_error: Option<(String, Option<ExpressionError>)>,
+}
+
+impl Default for UIState {
+    fn default() -> Self {
+        Self {
+            is_gen_window_expanded: false,
+            live_update: false,
+            config_path: "terrain.ron".to_string(),
+            config_status: None,
+            world_path: "world.terrain".to_string(),
+            export_path: "terrain.obj".to_string(),
+            export_options: ExportOptions::default(),
+            expression_error: None,
+        }
+    }
+}
+
+/// Events the UI sends to the terrain generator
+#[derive(SystemParam)]
+pub struct TerrainEvents<'w> {
+    generate: EventWriter<'w, GenerateTerrainEvent>,
+    history: EventWriter<'w, HistoryCommand>,
+    save: EventWriter<'w, SaveWorldEvent>,
+    load: EventWriter<'w, LoadWorldEvent>,
+    export: EventWriter<'w, ExportTerrainEvent>,
 }
 
 pub fn ui_system(
     mut contexts: EguiContexts,
     mut generation_config: ResMut<TerrainGeneratorConfig>,
-    mut generate_terrain_writer: EventWriter<GenerateTerrainEvent>,
+    mut events: TerrainEvents,
     mut ui_state: Local<UIState>,
+    mut brush: ResMut<Brush>,
+    history: Res<EditHistory>,
 ) {
+    let ui_state = &mut *ui_state;
+    let previous_config = generation_config.clone();
+
     TopBottomPanel::top("top_panel")
         .resizable(false)
         .show(contexts.ctx_mut(), |ui| {
@@ -66,15 +126,601 @@ pub fn ui_system(
                 ui.heading("Isolevel");
                 ui.add(DragValue::new(&mut generation_config.isolevel).speed(0.1));
                 ui.end_row();
+
+                ui.heading("Infinite world");
+                ui.checkbox(&mut generation_config.streaming, "");
+                ui.end_row();
+
+                ui.heading("View radius");
+                ui.add(
+                    DragValue::new(&mut generation_config.view_radius)
+                        .clamp_range(1..=MAX_VIEW_RADIUS),
+                );
+                ui.end_row();
+
+                ui.heading("LOD levels");
+                ui.add(DragValue::new(&mut generation_config.lod_levels).clamp_range(0..=3));
+                ui.end_row();
+
+                ui.heading("LOD distance");
+                ui.add(
+                    DragValue::new(&mut generation_config.lod_distance)
+                        .speed(0.1)
+                        .clamp_range(0.5f32..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.heading("Chunks per frame");
+                ui.add(
+                    DragValue::new(&mut generation_config.chunks_per_frame)
+                        .clamp_range(1u32..=u32::MAX),
+                );
+                ui.end_row();
+
+                ui.heading("Mesher");
+                enum_combo(
+                    ui,
+                    "mesher",
+                    &mut generation_config.mesher,
+                    &[
+                        MesherKind::MarchingCubes,
+                        MesherKind::MarchingCubes33,
+                        MesherKind::MarchingTetrahedra,
+                        MesherKind::SurfaceNets,
+                        MesherKind::DualContouring,
+                    ],
+                );
+                if generation_config.mesher == MesherKind::MarchingCubes33 {
+                    ui.label("Partial: tunnels between three contours aren't built");
+                }
+                ui.end_row();
+
+                ui.heading("Normals");
+                enum_combo(
+                    ui,
+                    "normals",
+                    &mut generation_config.normals,
+                    &[NormalMode::FaceWeighted, NormalMode::Gradient],
+                );
+                ui.end_row();
+
+                ui.heading("Index format");
+                enum_combo(
+                    ui,
+                    "index_format",
+                    &mut generation_config.index_format,
+                    &[MeshIndexFormat::Auto, MeshIndexFormat::U32],
+                );
+                ui.end_row();
+            });
+            ui.heading("Density");
+            Grid::new("terrain_density_settings_grid").show(ui, |ui| {
+                ui.heading("Seed");
+                ui.add(DragValue::new(&mut generation_config.seed));
+                ui.end_row();
+
+                ui.heading("Function");
+                density_settings_combo(ui, &mut generation_config.density);
+                ui.end_row();
+
+                let seed = generation_config.seed;
+                match &mut generation_config.density {
+                    DensitySettings::Noise(settings) => noise_settings_rows(ui, settings),
+                    DensitySettings::Expression { source, t } => {
+                        expression_rows(ui, source, t, seed, &mut ui_state.expression_error)
+                    }
+                    _ => {}
+                }
+            });
+
+            ui.heading("Materials");
+            Grid::new("terrain_material_settings_grid").show(ui, |ui| {
+                let rules = &mut generation_config.materials;
+                ui.heading("Cliff angle");
+                ui.add(Slider::new(&mut rules.cliff_angle, 0f32..=90f32).suffix("°"));
+                ui.end_row();
+
+                ui.heading("Snow height");
+                ui.add(DragValue::new(&mut rules.snow_height).speed(0.1));
+                ui.end_row();
+
+                ui.heading("Sand height");
+                ui.add(DragValue::new(&mut rules.sand_height).speed(0.1));
+                ui.end_row();
+
+                ui.heading("Soil depth");
+                ui.add(
+                    DragValue::new(&mut rules.soil_depth)
+                        .speed(0.1)
+                        .clamp_range(0f32..=f32::MAX),
+                );
+                ui.end_row();
+            });
+
+            ui.heading("Coloring");
+            Grid::new("terrain_coloring_settings_grid").show(ui, |ui| {
+                let coloring = &mut generation_config.coloring;
+                ui.heading("Colors");
+                enum_combo(
+                    ui,
+                    "color_mode",
+                    &mut coloring.mode,
+                    &[ColorMode::Materials, ColorMode::Ramps],
+                );
+                ui.end_row();
+
+                if coloring.mode == ColorMode::Ramps {
+                    color_ramp_rows(ui, "Height", &mut coloring.height);
+                    color_ramp_rows(ui, "Slope", &mut coloring.slope);
+                    color_ramp_rows(ui, "Curvature", &mut coloring.curvature);
+                }
+            });
+
+            ui.heading("Splatting");
+            Grid::new("terrain_splatting_settings_grid").show(ui, |ui| {
+                let splatting = &mut generation_config.splatting;
+                ui.heading("Triplanar material");
+                ui.checkbox(&mut splatting.enabled, "");
+                ui.end_row();
+
+                if splatting.enabled {
+                    ui.heading("Texture scale");
+                    ui.add(
+                        DragValue::new(&mut splatting.texture_scale)
+                            .speed(0.01)
+                            .clamp_range(0.001f32..=f32::MAX),
+                    );
+                    ui.end_row();
+
+                    ui.heading("Blend sharpness");
+                    ui.add(Slider::new(&mut splatting.blend_sharpness, 1f32..=16f32));
+                    ui.end_row();
+
+                    for (i, layer) in splatting
+                        .layers
+                        .iter_mut()
+                        .take(MAX_SPLAT_LAYERS)
+                        .enumerate()
+                    {
+                        splat_layer_rows(ui, i, layer);
+                    }
+                }
+            });
+
+            ui.heading("Sculpting");
+            ui.label("Left click builds, shift + left click digs");
+            Grid::new("terrain_sculpting_settings_grid").show(ui, |ui| {
+                ui.heading("Brush");
+                enum_combo(
+                    ui,
+                    "brush_mode",
+                    &mut brush.mode,
+                    &[
+                        BrushMode::Sphere,
+                        BrushMode::Cube,
+                        BrushMode::Smooth,
+                        BrushMode::Flatten,
+                    ],
+                );
+                ui.end_row();
+
+                ui.heading("Radius");
+                ui.add(
+                    DragValue::new(&mut brush.radius)
+                        .speed(0.1)
+                        .clamp_range(0.1f32..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.heading("Strength");
+                ui.add(
+                    DragValue::new(&mut brush.strength)
+                        .speed(0.1)
+                        .clamp_range(0f32..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.heading("Undo steps");
+                ui.add(DragValue::new(&mut generation_config.history_size));
+                ui.end_row();
+            });
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(history.can_undo(), |ui| {
+                    if ui.button("Undo").on_hover_text("Ctrl + Z").clicked() {
+                        events.history.send(HistoryCommand::Undo);
+                    }
+                });
+                ui.add_enabled_ui(history.can_redo(), |ui| {
+                    if ui.button("Redo").on_hover_text("Ctrl + Y").clicked() {
+                        events.history.send(HistoryCommand::Redo);
+                    }
+                });
             });
+
             ui.heading("Debug");
             ui.checkbox(&mut generation_config.show_gizmos, "Show gizmo");
 
             ui.add_space(10f32);
+            ui.checkbox(&mut ui_state.live_update, "Regenerate on change");
             ui.vertical_centered_justified(|ui| {
                 if ui.button("Generate").clicked() {
-                    generate_terrain_writer.send(GenerateTerrainEvent);
+                    events.generate.send(GenerateTerrainEvent);
+                }
+            });
+
+            ui.add_space(10f32);
+            ui.heading("Config");
+            config_file_ui(
+                ui,
+                &mut generation_config,
+                &mut ui_state.config_path,
+                &mut ui_state.config_status,
+            );
+
+            ui.heading("World");
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut ui_state.world_path);
+            });
+            ui.horizontal(|ui| {
+                let path = PathBuf::from(&ui_state.world_path);
+                if ui.button("Save").clicked() {
+                    events.save.send(SaveWorldEvent { path: path.clone() });
+                }
+                if ui.button("Load").clicked() {
+                    events.load.send(LoadWorldEvent { path });
                 }
             });
+
+            ui.heading("Export");
+            Grid::new("terrain_export_settings_grid").show(ui, |ui| {
+                ui.heading("File");
+                ui.text_edit_singleline(&mut ui_state.export_path);
+                ui.end_row();
+
+                ui.heading("Format");
+                let options = &mut ui_state.export_options;
+                enum_combo(
+                    ui,
+                    "export_format",
+                    &mut options.format,
+                    &[
+                        ExportFormat::Obj,
+                        ExportFormat::PlyAscii,
+                        ExportFormat::PlyBinary,
+                        ExportFormat::Stl,
+                        ExportFormat::Glb,
+                    ],
+                );
+                ui.end_row();
+
+                ui.heading("Per chunk");
+                ui.checkbox(&mut options.per_chunk, "");
+                ui.end_row();
+
+                if matches!(
+                    options.format,
+                    ExportFormat::PlyAscii | ExportFormat::PlyBinary
+                ) {
+                    ui.heading("Vertex colors");
+                    ui.checkbox(&mut options.vertex_colors, "");
+                    ui.end_row();
+                }
+            });
+            ui.vertical_centered_justified(|ui| {
+                if ui.button("Export").clicked() {
+                    events.export.send(ExportTerrainEvent {
+                        path: PathBuf::from(&ui_state.export_path),
+                        options: ui_state.export_options,
+                    });
+                }
+            });
+        });
+
+    if ui_state.live_update && *generation_config != previous_config {
+        events.generate.send(GenerateTerrainEvent);
+    }
+}
+
+/// Preset dropdown and saving or loading the whole config
+fn config_file_ui(
+    ui: &mut Ui,
+    config: &mut TerrainGeneratorConfig,
+    path: &mut String,
+    status: &mut Option<Result<String, String>>,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Preset");
+        ComboBox::from_id_source("config_preset")
+            .selected_text("Apply preset")
+            .show_ui(ui, |ui| {
+                for preset in Preset::ALL {
+                    if ui.selectable_label(false, preset.name()).clicked() {
+                        config.take_setup(preset.config());
+                        *status = Some(Ok(format!("Applied {}", preset.name())));
+                    }
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        ui.label("File");
+        ui.text_edit_singleline(path);
+    });
+    ui.horizontal(|ui| {
+        let path = PathBuf::from(&*path);
+        if ui.button("Save").clicked() {
+            *status = Some(
+                config
+                    .save(&path)
+                    .map(|()| format!("Saved {}", path.display()))
+                    .map_err(|err| format!("Can't save {}: {err}", path.display())),
+            );
+        }
+        if ui.button("Load").clicked() {
+            *status = Some(match TerrainGeneratorConfig::load(&path) {
+                Ok(loaded) => {
+                    config.take_setup(loaded);
+                    Ok(format!("Loaded {}", path.display()))
+                }
+                Err(err) => Err(format!("Can't load {}: {err}", path.display())),
+            });
+        }
+    });
+    match &*status {
+        Some(Ok(message)) => {
+            ui.label(message.as_str());
+        }
+        Some(Err(message)) => {
+            ui.colored_label(Color32::RED, message.as_str());
+        }
+        None => {}
+    }
+}
+
+fn density_settings_combo(ui: &mut Ui, density: &mut DensitySettings) {
+    let selected_text = match density {
+        DensitySettings::Custom => "Custom",
+        DensitySettings::Plane => "Plane",
+        DensitySettings::Noise(_) => "Noise",
+        DensitySettings::Graph(_) => "Graph",
+        DensitySettings::Expression { .. } => "Expression",
+    };
+    ComboBox::from_id_source("density_settings")
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            if ui
+                .selectable_label(matches!(density, DensitySettings::Custom), "Custom")
+                .clicked()
+            {
+                *density = DensitySettings::Custom;
+            }
+            if ui
+                .selectable_label(matches!(density, DensitySettings::Plane), "Plane")
+                .clicked()
+            {
+                *density = DensitySettings::Plane;
+            }
+            if ui
+                .selectable_label(matches!(density, DensitySettings::Noise(_)), "Noise")
+                .clicked()
+                && !matches!(density, DensitySettings::Noise(_))
+            {
+                *density = DensitySettings::Noise(NoiseSettings::default());
+            }
+            if ui
+                .selectable_label(matches!(density, DensitySettings::Graph(_)), "Graph")
+                .clicked()
+                && !matches!(density, DensitySettings::Graph(_))
+            {
+                *density = DensitySettings::Graph(DensityGraph::default());
+            }
+            if ui
+                .selectable_label(
+                    matches!(density, DensitySettings::Expression { .. }),
+                    "Expression",
+                )
+                .clicked()
+                && !matches!(density, DensitySettings::Expression { .. })
+            {
+                *density = DensitySettings::Expression {
+                    source: "y - 4 - 3*fbm(x*0.1, z*0.1)".to_string(),
+                    t: 0f32,
+                };
+            }
+        });
+}
+
+fn noise_settings_rows(ui: &mut Ui, settings: &mut NoiseSettings) {
+    use FractalKind::*;
+    use NoiseKind::*;
+
+    ui.heading("Noise");
+    enum_combo(
+        ui,
+        "noise_kind",
+        &mut settings.noise,
+        &[Perlin, Simplex, Value, Worley],
+    );
+    ui.end_row();
+
+    ui.heading("Dimensions");
+    enum_combo(
+        ui,
+        "noise_dimensions",
+        &mut settings.dimensions,
+        &[NoiseDimensions::Surface, NoiseDimensions::Volume],
+    );
+    ui.end_row();
+
+    ui.heading("Frequency");
+    ui.add(
+        DragValue::new(&mut settings.frequency)
+            .speed(0.001)
+            .clamp_range(0f32..=f32::MAX),
+    );
+    ui.end_row();
+
+    ui.heading("Amplitude");
+    ui.add(DragValue::new(&mut settings.amplitude).speed(0.1));
+    ui.end_row();
+
+    ui.heading("Ground level");
+    ui.add(DragValue::new(&mut settings.ground_level).speed(0.1));
+    ui.end_row();
+
+    let fractal = &mut settings.fractal;
+    ui.heading("Fractal");
+    enum_combo(
+        ui,
+        "fractal_kind",
+        &mut fractal.kind,
+        &[Fbm, Ridged, Billow, Turbulence],
+    );
+    ui.end_row();
+
+    ui.heading("Octaves");
+    ui.add(Slider::new(&mut fractal.octaves, 1..=12));
+    ui.end_row();
+
+    ui.heading("Lacunarity");
+    ui.add(Slider::new(&mut fractal.lacunarity, 1f32..=4f32));
+    ui.end_row();
+
+    ui.heading("Gain");
+    ui.add(Slider::new(&mut fractal.gain, 0f32..=1f32));
+    ui.end_row();
+
+    ui.heading("Octave offset");
+    ui.horizontal(|ui| {
+        let mut offset = fractal.octave_offset.to_array();
+        for (i, l) in offset.iter_mut().zip(["x", "y", "z"]) {
+            ui.label(format!("{l}: "));
+            ui.add(DragValue::new(i).speed(0.1));
+        }
+        fractal.octave_offset = Vec3::from_array(offset);
+    });
+    ui.end_row();
+
+    if fractal.kind == Ridged {
+        ui.heading("Ridge offset");
+        ui.add(Slider::new(&mut fractal.ridge_offset, 0.1f32..=2f32));
+        ui.end_row();
+    }
+}
+
+fn expression_rows(
+    ui: &mut Ui,
+    source: &mut String,
+    t: &mut f32,
+    seed: u32,
+    parsed: &mut Option<(String, Option<ExpressionError>)>,
+) {
+    ui.heading("Expression");
+    ui.vertical(|ui| {
+        ui.add(TextEdit::multiline(source).code_editor().desired_rows(2));
+        // The source can also change by loading a config or a preset
+        if !matches!(parsed, Some((parsed_source, _)) if parsed_source == source) {
+            *parsed = Some((source.clone(), Expression::parse(source, seed).err()));
+        }
+        if let Some((_, Some(err))) = parsed {
+            ui.colored_label(Color32::RED, err.to_string());
+        }
+    });
+    ui.end_row();
+
+    ui.heading("t");
+    ui.add(DragValue::new(t).speed(0.01));
+    ui.end_row();
+}
+
+fn color_ramp_rows(ui: &mut Ui, name: &str, ramp: &mut ColorRamp) {
+    ui.heading(name);
+    ui.vertical(|ui| {
+        let mut resort = false;
+        let mut removed = None;
+        for (i, (position, color)) in ramp.stops.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let response = ui.add(DragValue::new(position).speed(0.1));
+                // Sorting while dragging would move the stop away from the cursor
+                resort |= response.drag_released() || response.lost_focus();
+
+                let mut rgba = color.as_rgba_u8();
+                if ui.color_edit_button_srgba_unmultiplied(&mut rgba).changed() {
+                    *color = Color::rgba_u8(rgba[0], rgba[1], rgba[2], rgba[3]);
+                }
+                if ui.small_button("-").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            ramp.stops.remove(i);
+        }
+        if ui.small_button("+").clicked() {
+            let stop = ramp
+                .stops
+                .last()
+                .map_or((0f32, Color::WHITE), |&(position, color)| {
+                    (position + 1f32, color)
+                });
+            ramp.stops.push(stop);
+        }
+        if resort {
+            ramp.sort();
+        }
+    });
+    ui.end_row();
+}
+
+fn splat_layer_rows(ui: &mut Ui, i: usize, layer: &mut SplatLayer) {
+    ui.heading(format!("Layer {i}"));
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            let mut texture = layer.texture.clone().unwrap_or_default();
+            ui.label("Texture: ");
+            if ui.text_edit_singleline(&mut texture).changed() {
+                layer.texture = (!texture.is_empty()).then_some(texture);
+            }
+            let mut rgba = layer.tint.as_rgba_u8();
+            if ui.color_edit_button_srgba_unmultiplied(&mut rgba).changed() {
+                layer.tint = Color::rgba_u8(rgba[0], rgba[1], rgba[2], rgba[3]);
+            }
+        });
+        ui.horizontal(|ui| {
+            for material in MaterialId::ALL {
+                let mut covered = layer.materials.contains(&material);
+                if ui.checkbox(&mut covered, format!("{material:?}")).changed() {
+                    if covered {
+                        layer.materials.push(material);
+                    } else {
+                        layer.materials.retain(|&other| other != material);
+                    }
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Slope: ");
+            ui.add(DragValue::new(&mut layer.slope.x).clamp_range(0f32..=layer.slope.y));
+            ui.add(DragValue::new(&mut layer.slope.y).clamp_range(layer.slope.x..=180f32));
+            let mut limited = layer.height.is_some();
+            if ui.checkbox(&mut limited, "Height: ").changed() {
+                layer.height = limited.then_some(Vec2::new(0f32, 16f32));
+            }
+            if let Some(height) = &mut layer.height {
+                ui.add(DragValue::new(&mut height.x).speed(0.1));
+                ui.add(DragValue::new(&mut height.y).speed(0.1));
+            }
+        });
+    });
+    ui.end_row();
+}
+
+fn enum_combo<T: Debug + PartialEq + Copy>(ui: &mut Ui, id: &str, value: &mut T, variants: &[T]) {
+    ComboBox::from_id_source(id)
+        .selected_text(format!("{value:?}"))
+        .show_ui(ui, |ui| {
+            for variant in variants {
+                ui.selectable_value(value, *variant, format!("{variant:?}"));
+            }
         });
 }
@@ -0,0 +1,54 @@
+use bevy::{prelude::*, render::mesh::Indices};
+use terrain_procgen::generation::{
+    coloring::vertex_curvatures, density::Sphere, mesh_chunk, ColorRamp, MaterialRules,
+    TerrainChunk, TerrainGeneratorConfig,
+};
+
+#[test]
+fn ramp_interpolates_between_stops() {
+    let ramp = ColorRamp {
+        stops: vec![(0f32, Color::BLACK), (2f32, Color::WHITE)],
+    };
+    assert_eq!(ramp.sample(-1f32), Vec4::new(0f32, 0f32, 0f32, 1f32));
+    assert_eq!(ramp.sample(1f32), Vec4::new(0.5f32, 0.5f32, 0.5f32, 1f32));
+    assert_eq!(ramp.sample(3f32), Vec4::ONE);
+    assert_eq!(ColorRamp { stops: vec![] }.sample(0f32), Vec4::ZERO);
+}
+
+#[test]
+fn sphere_curvature_is_inverse_radius() {
+    let radius = 6f32;
+    let sphere = Sphere {
+        center: Vec3::splat(8f32),
+        radius,
+    };
+    let mut chunk = TerrainChunk::new(IVec3::ZERO, UVec3::splat(16), 1f32);
+    chunk.sample(&sphere, &MaterialRules::default());
+    let mesh = mesh_chunk(&chunk, &TerrainGeneratorConfig::default(), Some(&sphere));
+
+    let positions: Vec<Vec3> = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|positions| positions.as_float3())
+        .unwrap()
+        .iter()
+        .map(|&position| position.into())
+        .collect();
+    let normals: Vec<Vec3> = mesh
+        .attribute(Mesh::ATTRIBUTE_NORMAL)
+        .and_then(|normals| normals.as_float3())
+        .unwrap()
+        .iter()
+        .map(|&normal| normal.into())
+        .collect();
+    let Some(Indices::U16(indices)) = mesh.indices() else {
+        panic!("expected 16 bit indices");
+    };
+    let indices: Vec<u32> = indices.iter().map(|&idx| idx as u32).collect();
+
+    let curvatures = vertex_curvatures(&positions, &normals, &indices);
+    let mean = curvatures.iter().sum::<f32>() / curvatures.len() as f32;
+    assert!(
+        (mean - 1f32 / radius).abs() < 0.02f32,
+        "mean curvature {mean} of a sphere with radius {radius}"
+    );
+}
@@ -0,0 +1,51 @@
+//! Fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use terrain_procgen::generation::{
+    density::Sphere, mesh_chunk, ChunkMesh, MaterialRules, TerrainChunk, TerrainGeneratorConfig,
+};
+
+const SIZE: u32 = 8;
+
+/// Two chunks cutting a sphere in half, the second one is placed with `transform`
+pub fn chunk_meshes(transform: Transform) -> Vec<(IVec3, Transform, Mesh)> {
+    let config = TerrainGeneratorConfig {
+        chunk_size: UVec3::splat(SIZE),
+        ..default()
+    };
+    let sphere = Sphere {
+        center: Vec3::new(SIZE as f32, 4f32, 4f32),
+        radius: 3f32,
+    };
+    [(IVec3::ZERO, Transform::IDENTITY), (IVec3::X, transform)]
+        .into_iter()
+        .map(|(coord, transform)| {
+            let mut chunk = TerrainChunk::new(coord, config.chunk_size, 1f32);
+            chunk.sample(&sphere, &MaterialRules::default());
+            (coord, transform, mesh_chunk(&chunk, &config, None))
+        })
+        .collect()
+}
+
+/// Chunk meshes the way the exporter takes them
+pub fn export_input(meshes: &[(IVec3, Transform, Mesh)]) -> Vec<ChunkMesh<'_>> {
+    meshes
+        .iter()
+        .map(|(coord, transform, mesh)| ChunkMesh {
+            coord: *coord,
+            transform: *transform,
+            mesh,
+        })
+        .collect()
+}
+
+/// Directory for the files of a test, separate for every process
+/// so that concurrent test runs don't overwrite each other's files
+pub fn output_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("terrain_{test}_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
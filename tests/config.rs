@@ -0,0 +1,135 @@
+use std::fs;
+
+use bevy::prelude::*;
+use terrain_procgen::generation::{
+    generate_chunks, graph::DensityGraph, noise::NoiseSettings, ConfigFileError, ConfigFormat,
+    DensitySettings, Preset, TerrainGeneratorConfig,
+};
+
+mod common;
+
+use common::output_dir;
+
+fn configs() -> Vec<TerrainGeneratorConfig> {
+    let graph = TerrainGeneratorConfig {
+        density: DensitySettings::Graph(DensityGraph::default()),
+        ..Default::default()
+    };
+    Preset::ALL
+        .into_iter()
+        .map(Preset::config)
+        .chain([TerrainGeneratorConfig::default(), graph])
+        .collect()
+}
+
+#[test]
+fn configs_round_trip_through_ron_and_toml() {
+    for config in configs() {
+        for format in [ConfigFormat::Ron, ConfigFormat::Toml] {
+            let text = config.to_text(format).unwrap();
+            let parsed = TerrainGeneratorConfig::from_text(&text, format).unwrap();
+            assert_eq!(parsed, config, "{format:?}:\n{text}");
+        }
+    }
+}
+
+#[test]
+fn presets_generate_terrain() {
+    for preset in Preset::ALL {
+        assert_eq!(Preset::from_name(preset.name()), Some(preset));
+        let config = preset.config();
+        let density = config.density.build(config.seed).unwrap().unwrap();
+        let vertices: usize = generate_chunks(&config, density.0.as_ref())
+            .iter()
+            .map(|(_, mesh)| mesh.count_vertices())
+            .sum();
+        assert!(vertices > 0, "{} has no surface", preset.name());
+    }
+}
+
+#[test]
+fn file_format_follows_the_extension() {
+    let dir = output_dir("config_files");
+    let config = Preset::Mountains.config();
+
+    let toml_path = dir.join("mountains.toml");
+    config.save(&toml_path).unwrap();
+    let text = fs::read_to_string(&toml_path).unwrap();
+    assert!(toml::from_str::<toml::Table>(&text).is_ok());
+    assert_eq!(TerrainGeneratorConfig::load(&toml_path).unwrap(), config);
+
+    let ron_path = dir.join("mountains.ron");
+    config.save(&ron_path).unwrap();
+    assert_eq!(
+        fs::read_to_string(&ron_path).unwrap(),
+        Preset::Mountains.source()
+    );
+
+    fs::write(&toml_path, "seed = \"not a number\"").unwrap();
+    assert!(matches!(
+        TerrainGeneratorConfig::load(&toml_path),
+        Err(ConfigFileError::Parse(_))
+    ));
+    assert!(matches!(
+        TerrainGeneratorConfig::load(dir.join("missing.ron")),
+        Err(ConfigFileError::Io(_))
+    ));
+}
+
+#[test]
+fn missing_fields_take_their_defaults() {
+    let expected = TerrainGeneratorConfig {
+        seed: 5,
+        density: DensitySettings::Noise(NoiseSettings {
+            frequency: 0.1f32,
+            ..default()
+        }),
+        ..default()
+    };
+    for (text, format) in [
+        (
+            "(seed: 5, density: Noise((frequency: 0.1)))",
+            ConfigFormat::Ron,
+        ),
+        (
+            "seed = 5\n[density.Noise]\nfrequency = 0.1",
+            ConfigFormat::Toml,
+        ),
+    ] {
+        let parsed = TerrainGeneratorConfig::from_text(text, format).unwrap();
+        assert_eq!(parsed, expected, "{format:?}");
+    }
+}
+
+#[test]
+fn editor_settings_stay_out_of_files() {
+    let config = TerrainGeneratorConfig {
+        history_size: 3,
+        show_gizmos: true,
+        ..default()
+    };
+    for format in [ConfigFormat::Ron, ConfigFormat::Toml] {
+        let text = config.to_text(format).unwrap();
+        assert!(!text.contains("history_size") && !text.contains("show_gizmos"));
+        // Unbounded splat heights aren't written out as the largest floats
+        assert!(!text.contains("340282") && !text.contains("e38"), "{text}");
+    }
+
+    let mut current = config.clone();
+    current.take_setup(Preset::Caves.config());
+    assert_eq!((current.history_size, current.show_gizmos), (3, true));
+    assert_eq!(current.density, Preset::Caves.config().density);
+}
+
+#[test]
+fn splat_heights_without_some_still_load() {
+    let text = "(splatting: (layers: [(height: (-1.0, 2.0)), ()]))";
+    let config = TerrainGeneratorConfig::from_text(text, ConfigFormat::Ron).unwrap();
+    let heights: Vec<_> = config
+        .splatting
+        .layers
+        .iter()
+        .map(|layer| layer.height)
+        .collect();
+    assert_eq!(heights, [Some(Vec2::new(-1f32, 2f32)), None]);
+}
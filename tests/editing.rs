@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use terrain_procgen::generation::{
+    density::Plane, editing::raycast_terrain, BrushMode, EditHistory, MaterialRules, TerrainChunk,
+    TerrainEdit,
+};
+
+const SIZE: u32 = 8;
+
+/// Flat ground with the surface at `height`
+fn ground(height: f32) -> Plane {
+    Plane {
+        normal: Vec3::Y,
+        offset: height,
+    }
+}
+
+fn ground_chunk(coord: IVec3, height: f32) -> TerrainChunk {
+    let mut chunk = TerrainChunk::new(coord, UVec3::splat(SIZE), 1f32);
+    chunk.sample(&ground(height), &MaterialRules::default());
+    chunk
+}
+
+#[test]
+fn sphere_brush_digs_and_builds() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let center = Vec3::new(4f32, 4f32, 4f32);
+    let mut edit = TerrainEdit {
+        center,
+        radius: 2f32,
+        strength: -3f32,
+        mode: BrushMode::Sphere,
+    };
+
+    assert!(chunk.apply_edit(&edit));
+    let below = center - Vec3::Y * 0.5f32;
+    assert!(chunk.value_at(below).unwrap() > 0f32, "hole wasn't dug");
+
+    edit.strength = 6f32;
+    assert!(chunk.apply_edit(&edit));
+    let above = center + Vec3::Y * 0.5f32;
+    assert!(chunk.value_at(above).unwrap() < 0f32, "hill wasn't built");
+}
+
+#[test]
+fn brush_outside_of_the_chunk_touches_nothing() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let edit = TerrainEdit {
+        center: Vec3::new(30f32, 4f32, 4f32),
+        radius: 2f32,
+        strength: 1f32,
+        mode: BrushMode::Cube,
+    };
+    assert!(!chunk.apply_edit(&edit));
+}
+
+#[test]
+fn edits_match_on_shared_points() {
+    let mut left = ground_chunk(IVec3::ZERO, 4f32);
+    let mut right = ground_chunk(IVec3::X, 4f32);
+    for mode in [
+        BrushMode::Sphere,
+        BrushMode::Cube,
+        BrushMode::Smooth,
+        BrushMode::Flatten,
+    ] {
+        // Off the border, so that the chunks' differences don't mirror each other
+        let edit = TerrainEdit {
+            center: Vec3::new(SIZE as f32 - 0.5f32, 5f32, 4f32),
+            radius: 3f32,
+            strength: 0.7f32,
+            mode,
+        };
+        // A stroke applies the brush every frame, differences would pile up
+        for _ in 0..20 {
+            assert!(left.apply_edit(&edit));
+            assert!(right.apply_edit(&edit));
+            if mode == BrushMode::Smooth {
+                left.copy_shared_points(&right);
+                right.copy_shared_points(&left);
+            }
+        }
+    }
+
+    for y in 0..=SIZE {
+        for z in 0..=SIZE {
+            let border = Vec3::new(SIZE as f32, y as f32, z as f32);
+            assert_eq!(left.value_at(border), right.value_at(border), "at {border}");
+        }
+    }
+}
+
+#[test]
+fn flatten_pulls_the_surface_to_the_center() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 2f32);
+    let edit = TerrainEdit {
+        center: Vec3::new(4f32, 5f32, 4f32),
+        radius: 3f32,
+        strength: 1f32,
+        mode: BrushMode::Flatten,
+    };
+    assert!(chunk.apply_edit(&edit));
+    // The brush has full strength at its center only
+    assert_eq!(chunk.value_at(edit.center), Some(0f32));
+}
+
+#[test]
+fn raycast_hits_the_ground() {
+    let chunk = ground_chunk(IVec3::ZERO, 3.25f32);
+    let ray = Ray {
+        origin: Vec3::new(4.5f32, 20f32, 4.5f32),
+        direction: -Vec3::Y,
+    };
+    let hit = raycast_terrain(ray, 100f32, 0.5f32, 0f32, |pos| chunk.value_at(pos)).unwrap();
+    assert!((hit.y - 3.25f32).abs() < 1e-3f32, "hit at {hit}");
+
+    let miss = Ray {
+        origin: Vec3::new(4.5f32, 20f32, 4.5f32),
+        direction: Vec3::Y,
+    };
+    assert_eq!(
+        raycast_terrain(miss, 100f32, 0.5f32, 0f32, |pos| chunk.value_at(pos)),
+        None
+    );
+}
+
+/// Values of all points inside of the chunk
+fn interior_values(chunk: &TerrainChunk) -> Vec<Option<f32>> {
+    let mut values = Vec::new();
+    for z in 0..=SIZE {
+        for y in 0..=SIZE {
+            for x in 0..=SIZE {
+                values.push(chunk.value_at(Vec3::new(x as f32, y as f32, z as f32)));
+            }
+        }
+    }
+    values
+}
+
+#[test]
+fn undo_and_redo_restore_the_points() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let original = interior_values(&chunk);
+    let mut history = EditHistory::default();
+    let edit = TerrainEdit {
+        center: Vec3::new(4f32, 4f32, 4f32),
+        radius: 2f32,
+        strength: 1f32,
+        mode: BrushMode::Sphere,
+    };
+
+    assert!(history.apply_edit(&mut chunk, &edit));
+    history.end_stroke(8);
+    let edited = interior_values(&chunk);
+    assert_ne!(edited, original);
+
+    let step = history.undo(8).unwrap();
+    assert!(step.revert(&mut chunk));
+    assert_eq!(interior_values(&chunk), original);
+    assert!(!history.can_undo());
+
+    let step = history.redo(8).unwrap();
+    assert!(step.reapply(&mut chunk));
+    assert_eq!(interior_values(&chunk), edited);
+    assert!(!history.can_redo());
+}
+
+#[test]
+fn a_stroke_is_undone_at_once() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let original = interior_values(&chunk);
+    let mut history = EditHistory::default();
+    for x in 2..6 {
+        let edit = TerrainEdit {
+            center: Vec3::new(x as f32, 4f32, 4f32),
+            radius: 2f32,
+            strength: 0.5f32,
+            mode: BrushMode::Sphere,
+        };
+        assert!(history.apply_edit(&mut chunk, &edit));
+    }
+
+    // Undoing ends the stroke in progress
+    let step = history.undo(8).unwrap();
+    assert!(step.revert(&mut chunk));
+    assert_eq!(interior_values(&chunk), original);
+    assert!(history.undo(8).is_none());
+}
+
+#[test]
+fn history_keeps_the_latest_strokes() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let mut history = EditHistory::default();
+    for _ in 0..5 {
+        let edit = TerrainEdit {
+            center: Vec3::new(4f32, 4f32, 4f32),
+            radius: 2f32,
+            strength: 0.5f32,
+            mode: BrushMode::Sphere,
+        };
+        history.apply_edit(&mut chunk, &edit);
+        history.end_stroke(3);
+    }
+
+    let mut undone = 0;
+    while let Some(step) = history.undo(3) {
+        step.revert(&mut chunk);
+        undone += 1;
+    }
+    assert_eq!(undone, 3);
+
+    // A new stroke drops the undone ones
+    let edit = TerrainEdit {
+        center: Vec3::new(4f32, 4f32, 4f32),
+        radius: 2f32,
+        strength: 0.5f32,
+        mode: BrushMode::Cube,
+    };
+    history.apply_edit(&mut chunk, &edit);
+    history.end_stroke(3);
+    assert!(!history.can_redo());
+}
+
+#[test]
+fn undo_skips_chunks_of_another_lod() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let mut history = EditHistory::default();
+    let edit = TerrainEdit {
+        center: Vec3::new(4f32, 4f32, 4f32),
+        radius: 2f32,
+        strength: 1f32,
+        mode: BrushMode::Sphere,
+    };
+    history.apply_edit(&mut chunk, &edit);
+
+    let mut coarse = TerrainChunk::with_lod(IVec3::ZERO, UVec3::splat(SIZE), 1f32, 1);
+    let step = history.undo(8).unwrap();
+    assert!(!step.revert(&mut coarse));
+}
+
+#[test]
+fn edits_survive_level_of_detail_changes() {
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let edit = TerrainEdit {
+        center: Vec3::new(4f32, 4f32, 4f32),
+        radius: 3f32,
+        strength: 2f32,
+        mode: BrushMode::Sphere,
+    };
+    assert!(chunk.apply_edit(&edit));
+    let edited = interior_values(&chunk);
+
+    assert!(chunk.set_lod(1, UVec3::splat(SIZE), 1f32));
+    chunk.sample(&ground(4f32), &MaterialRules::default());
+    // Coarse points share their positions with every second point
+    assert_eq!(chunk.value_at(edit.center), edited[4 * 81 + 4 * 9 + 4]);
+
+    assert!(!chunk.set_lod(0, UVec3::splat(SIZE), 1f32));
+    assert_eq!(interior_values(&chunk), edited);
+}
+
+#[test]
+fn coarse_edits_reach_the_kept_points() {
+    let first = TerrainEdit {
+        center: Vec3::new(4f32, 4f32, 4f32),
+        radius: 3f32,
+        strength: 2f32,
+        mode: BrushMode::Sphere,
+    };
+    let second = TerrainEdit {
+        center: Vec3::new(2f32, 4f32, 6f32),
+        radius: 2f32,
+        strength: -1f32,
+        mode: BrushMode::Cube,
+    };
+    let mut expected = ground_chunk(IVec3::ZERO, 4f32);
+    expected.apply_edit(&first);
+    expected.apply_edit(&second);
+
+    let mut chunk = ground_chunk(IVec3::ZERO, 4f32);
+    let mut history = EditHistory::default();
+    history.apply_edit(&mut chunk, &first);
+    history.end_stroke(8);
+    chunk.set_lod(1, UVec3::splat(SIZE), 1f32);
+    chunk.sample(&ground(4f32), &MaterialRules::default());
+    assert!(history.apply_edit(&mut chunk, &second));
+    history.end_stroke(8);
+
+    // Undoing at the coarse level reverts the kept points as well
+    let step = history.undo(8).unwrap();
+    assert!(step.revert(&mut chunk));
+    let step = history.redo(8).unwrap();
+    assert!(step.reapply(&mut chunk));
+
+    chunk.set_lod(2, UVec3::splat(SIZE), 1f32);
+    chunk.sample(&ground(4f32), &MaterialRules::default());
+    assert!(!chunk.set_lod(0, UVec3::splat(SIZE), 1f32));
+    assert_eq!(interior_values(&chunk), interior_values(&expected));
+}
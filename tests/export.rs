@@ -0,0 +1,158 @@
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use terrain_procgen::generation::{export_terrain, ExportFormat, ExportOptions};
+
+mod common;
+
+use common::{chunk_meshes, export_input, output_dir};
+
+fn export(test: &str, options: ExportOptions) -> Vec<PathBuf> {
+    export_meshes(test, &chunk_meshes(Transform::IDENTITY), options)
+}
+
+fn export_meshes(
+    test: &str,
+    meshes: &[(IVec3, Transform, Mesh)],
+    options: ExportOptions,
+) -> Vec<PathBuf> {
+    let dir = output_dir(&format!("export_{test}"));
+    export_terrain(dir.join("terrain"), &export_input(meshes), options).unwrap()
+}
+
+fn vertex_and_triangle_counts() -> (usize, usize) {
+    chunk_meshes(Transform::IDENTITY)
+        .iter()
+        .fold((0, 0), |(vertices, triangles), (_, _, mesh)| {
+            (
+                vertices + mesh.count_vertices(),
+                triangles + mesh.indices().unwrap().len() / 3,
+            )
+        })
+}
+
+#[test]
+fn obj_has_an_object_per_chunk() {
+    let (vertices, triangles) = vertex_and_triangle_counts();
+    let files = export(
+        "obj",
+        ExportOptions {
+            format: ExportFormat::Obj,
+            per_chunk: true,
+            ..default()
+        },
+    );
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].extension().unwrap(), "obj");
+
+    let obj = fs::read_to_string(&files[0]).unwrap();
+    let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+    assert_eq!(count("o "), 2);
+    assert_eq!(count("v "), vertices);
+    assert_eq!(count("vn "), vertices);
+    assert_eq!(count("f "), triangles);
+
+    // Indices are one based and run over all objects
+    let max_index = obj
+        .lines()
+        .filter_map(|line| line.strip_prefix("f "))
+        .flat_map(|face| face.split(' '))
+        .map(|corner| corner.split("//").next().unwrap().parse::<usize>().unwrap())
+        .max()
+        .unwrap();
+    assert_eq!(max_index, vertices);
+}
+
+#[test]
+fn obj_normal_indices_skip_objects_without_normals() {
+    let mut meshes = chunk_meshes(Transform::IDENTITY);
+    meshes[0].2.remove_attribute(Mesh::ATTRIBUTE_NORMAL);
+    let skipped = meshes[0].2.count_vertices();
+    let files = export_meshes(
+        "obj_without_normals",
+        &meshes,
+        ExportOptions {
+            format: ExportFormat::Obj,
+            per_chunk: true,
+            ..default()
+        },
+    );
+
+    let obj = fs::read_to_string(&files[0]).unwrap();
+    let normals = obj.lines().filter(|line| line.starts_with("vn ")).count();
+    assert_eq!(normals, meshes[1].2.count_vertices());
+    let mut objects = obj.split("o ").skip(1);
+    let without_normals = objects.next().unwrap();
+    assert!(!without_normals.contains("//"));
+
+    // The second object's normals are the first ones in the file
+    let faces = objects
+        .next()
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("f "));
+    for corner in faces.flat_map(|face| face.split(' ')) {
+        let (position, normal) = corner.split_once("//").unwrap();
+        let (position, normal) = (
+            position.parse::<usize>().unwrap(),
+            normal.parse::<usize>().unwrap(),
+        );
+        assert_eq!(position, normal + skipped, "{corner}");
+        assert!((1..=normals).contains(&normal), "{corner}");
+    }
+}
+
+#[test]
+fn ply_header_matches_the_data() {
+    let (vertices, triangles) = vertex_and_triangle_counts();
+    let options = ExportOptions {
+        format: ExportFormat::PlyAscii,
+        vertex_colors: true,
+        ..default()
+    };
+    let ascii = fs::read_to_string(&export("ply_ascii", options)[0]).unwrap();
+    let (header, body) = ascii.split_once("end_header\n").unwrap();
+    assert!(header.contains(&format!("element vertex {vertices}")));
+    assert!(header.contains(&format!("element face {triangles}")));
+    assert!(header.contains("property uchar red"));
+    assert_eq!(body.lines().count(), vertices + triangles);
+    // Position, normal and color
+    assert_eq!(body.lines().next().unwrap().split(' ').count(), 9);
+
+    let options = ExportOptions {
+        format: ExportFormat::PlyBinary,
+        ..default()
+    };
+    let binary = fs::read(&export("ply_binary", options)[0]).unwrap();
+    let header_end = binary
+        .windows(11)
+        .position(|window| window == b"end_header\n")
+        .unwrap()
+        + 11;
+    assert_eq!(
+        binary.len() - header_end,
+        vertices * 6 * 4 + triangles * (1 + 3 * 4)
+    );
+}
+
+#[test]
+fn stl_has_a_file_per_chunk() {
+    let files = export(
+        "stl",
+        ExportOptions {
+            format: ExportFormat::Stl,
+            per_chunk: true,
+            ..default()
+        },
+    );
+    assert_eq!(files.len(), 2);
+    for ((_, _, mesh), file) in chunk_meshes(Transform::IDENTITY).iter().zip(&files) {
+        let stl = fs::read(file).unwrap();
+        let triangles = mesh.indices().unwrap().len() / 3;
+        assert_eq!(
+            u32::from_le_bytes(stl[80..84].try_into().unwrap()),
+            triangles as u32
+        );
+        assert_eq!(stl.len(), 84 + triangles * 50);
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use terrain_procgen::generation::expression::{Expression, ExpressionError};
+
+fn evaluate(source: &str, pos: Vec3) -> f32 {
+    Expression::parse(source, 0).unwrap().evaluate(pos)
+}
+
+fn error(source: &str) -> ExpressionError {
+    Expression::parse(source, 0).unwrap_err()
+}
+
+#[test]
+fn operators_follow_precedence() {
+    let pos = Vec3::new(2f32, 3f32, 4f32);
+    for (source, expected) in [
+        ("1 + 2 * 3", 7f32),
+        ("(1 + 2) * 3", 9f32),
+        ("10 - 4 - 3", 3f32),
+        ("12 / 3 / 2", 2f32),
+        ("7 % 4 * 2", 6f32),
+        ("2 ^ 3 ^ 2", 512f32),
+        ("-2 ^ 2", -4f32),
+        ("2 ^ -1", 0.5f32),
+        ("--x", 2f32),
+        ("+y", 3f32),
+        ("x + y * z", 14f32),
+        ("x * y ^ 2 - z", 14f32),
+        ("-x % 3", 1f32),
+        ("1e2 + .5", 100.5f32),
+    ] {
+        assert_eq!(evaluate(source, pos), expected, "{source}");
+    }
+}
+
+#[test]
+fn math_functions_evaluate() {
+    let (x, y, z) = (0.3f32, 0.7f32, 2.5f32);
+    let pos = Vec3::new(x, y, z);
+    for (source, expected) in [
+        ("sin(x)", x.sin()),
+        ("cos(x)", x.cos()),
+        ("tan(x)", x.tan()),
+        ("asin(x)", x.asin()),
+        ("acos(x)", x.acos()),
+        ("atan(x)", x.atan()),
+        ("sinh(x)", x.sinh()),
+        ("cosh(x)", x.cosh()),
+        ("tanh(x)", x.tanh()),
+        ("abs(-z)", z),
+        ("sqrt(z)", z.sqrt()),
+        ("exp(x)", x.exp()),
+        ("ln(z)", z.ln()),
+        ("log2(z)", z.log2()),
+        ("log10(z)", z.log10()),
+        ("floor(z)", 2f32),
+        ("ceil(z)", 3f32),
+        ("round(-z)", -3f32),
+        ("fract(-x)", 0.7f32),
+        ("sign(-y)", -1f32),
+        ("min(x, y)", x),
+        ("max(x, y)", y),
+        ("pow(z, y)", z.powf(y)),
+        ("atan2(x, y)", x.atan2(y)),
+        ("mod(-z, 2)", 1.5f32),
+        ("step(y, x)", 0f32),
+        ("step(x, y)", 1f32),
+        ("clamp(z, x, y)", y),
+        ("lerp(x, z, 0.5)", 1.4f32),
+        ("smoothstep(0, 1, y)", 0.784f32),
+        ("pi", std::f32::consts::PI),
+        ("e", std::f32::consts::E),
+    ] {
+        let value = evaluate(source, pos);
+        assert!(
+            (value - expected).abs() < 1e-5,
+            "{source}: {value} != {expected}"
+        );
+    }
+}
+
+#[test]
+fn noise_functions_evaluate() {
+    for name in [
+        "perlin",
+        "simplex",
+        "value",
+        "worley",
+        "fbm",
+        "ridged",
+        "billow",
+        "turbulence",
+    ] {
+        for args in ["x, z", "x, y, z", "x, y, z, 1"] {
+            let source = format!("{name}({args})");
+            let expression = Expression::parse(&source, 3).unwrap();
+            let value = expression.evaluate(Vec3::new(1.3f32, 2.1f32, -0.7f32));
+            assert!(value.is_finite(), "{source}: {value}");
+        }
+        assert_eq!(
+            error(&format!("{name}(x)")),
+            ExpressionError {
+                message: format!("'{name}' takes 2 to 4 arguments but 1 were given"),
+                position: 0,
+            }
+        );
+    }
+}
+
+#[test]
+fn unknown_identifiers_are_reported() {
+    assert_eq!(
+        error("x + w"),
+        ExpressionError {
+            message: "unknown variable 'w'".to_string(),
+            position: 4,
+        }
+    );
+    assert_eq!(
+        error("2 * foo(x)"),
+        ExpressionError {
+            message: "unknown function 'foo'".to_string(),
+            position: 4,
+        }
+    );
+    assert_eq!(
+        error("sin(x, y)"),
+        ExpressionError {
+            message: "'sin' takes 1 arguments but 2 were given".to_string(),
+            position: 0,
+        }
+    );
+}
+
+#[test]
+fn unterminated_calls_report_the_end() {
+    for source in ["sin(x", "max(x, y", "fbm(x, 2 * z"] {
+        assert_eq!(
+            error(source),
+            ExpressionError {
+                message: "expected ',' or ')'".to_string(),
+                position: source.len(),
+            },
+            "{source}"
+        );
+    }
+    assert_eq!(error("max(x y)").position, 6);
+    assert_eq!(error("(x + 1").position, 6);
+    assert_eq!(error("sin(").message, "unexpected end of expression");
+}
@@ -0,0 +1,95 @@
+use std::{path::Path, thread, time::Duration};
+
+use bevy::{
+    asset::LoadState,
+    gltf::{Gltf, GltfMesh, GltfNode, GltfPlugin},
+    prelude::*,
+    scene::ScenePlugin,
+};
+use terrain_procgen::generation::{
+    export_terrain, terrain_standard_material, ExportFormat, ExportOptions,
+};
+
+mod common;
+
+use common::{chunk_meshes, export_input, output_dir};
+
+/// Load the file with Bevy's glTF loader and wait until it's done
+fn load_gltf(path: &Path) -> (App, Handle<Gltf>) {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        ScenePlugin,
+        GltfPlugin::default(),
+    ))
+    .add_asset::<Mesh>()
+    .add_asset::<Image>()
+    .add_asset::<StandardMaterial>();
+    app.finish();
+    app.cleanup();
+
+    let handle: Handle<Gltf> = app.world.resource::<AssetServer>().load(path.to_path_buf());
+    for _ in 0..1000 {
+        app.update();
+        match app.world.resource::<AssetServer>().get_load_state(&handle) {
+            LoadState::Loaded => return (app, handle),
+            LoadState::Failed => panic!("failed to load {}", path.display()),
+            _ => thread::sleep(Duration::from_millis(5)),
+        }
+    }
+    panic!("loading {} timed out", path.display());
+}
+
+#[test]
+fn glb_round_trips_through_bevy() {
+    // The second chunk is moved up
+    let chunks = chunk_meshes(Transform::from_xyz(0f32, 2f32, 0f32));
+    let dir = output_dir("export_glb");
+    let options = ExportOptions {
+        format: ExportFormat::Glb,
+        ..default()
+    };
+    let files = export_terrain(dir.join("terrain"), &export_input(&chunks), options).unwrap();
+    assert_eq!(files, vec![dir.join("terrain.glb")]);
+
+    let (app, handle) = load_gltf(&files[0]);
+    let gltf = app.world.resource::<Assets<Gltf>>().get(&handle).unwrap();
+    let nodes = app.world.resource::<Assets<GltfNode>>();
+    let gltf_meshes = app.world.resource::<Assets<GltfMesh>>();
+    let bevy_meshes = app.world.resource::<Assets<Mesh>>();
+    let materials = app.world.resource::<Assets<StandardMaterial>>();
+    assert_eq!(gltf.nodes.len(), chunks.len());
+
+    for (coord, transform, mesh) in &chunks {
+        let name = format!("chunk_{}_{}_{}", coord.x, coord.y, coord.z);
+        let node = nodes.get(&gltf.named_nodes[&name]).unwrap();
+        assert_eq!(node.transform, *transform);
+
+        let primitive = &gltf_meshes
+            .get(node.mesh.as_ref().unwrap())
+            .unwrap()
+            .primitives[0];
+        let loaded = bevy_meshes.get(&primitive.mesh).unwrap();
+        for attribute in [Mesh::ATTRIBUTE_POSITION, Mesh::ATTRIBUTE_NORMAL] {
+            assert_eq!(
+                loaded.attribute(attribute.id).unwrap().as_float3(),
+                mesh.attribute(attribute.id).unwrap().as_float3()
+            );
+        }
+        assert_eq!(
+            loaded.indices().unwrap().iter().collect::<Vec<_>>(),
+            mesh.indices().unwrap().iter().collect::<Vec<_>>()
+        );
+
+        let material = materials.get(primitive.material.as_ref().unwrap()).unwrap();
+        let expected = terrain_standard_material();
+        assert_eq!(
+            material.base_color.as_linear_rgba_f32(),
+            expected.base_color.as_linear_rgba_f32()
+        );
+        assert_eq!(material.metallic, expected.metallic);
+        assert_eq!(material.perceptual_roughness, expected.perceptual_roughness);
+        assert_eq!(material.double_sided, expected.double_sided);
+    }
+}
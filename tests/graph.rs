@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+use terrain_procgen::generation::{
+    graph::{DensityGraph, DensityNode, GraphError},
+    noise::NoiseSettings,
+    DensityError, DensitySettings, MaterialId,
+};
+
+fn sphere(center: Vec3, radius: f32) -> DensityNode {
+    DensityNode::Sphere { center, radius }
+}
+
+/// Graph using every kind of node
+fn every_node() -> DensityGraph {
+    use DensityNode::*;
+
+    DensityGraph {
+        root: Union {
+            nodes: vec![
+                Difference {
+                    base: Box::new(Intersection {
+                        nodes: vec![
+                            Cuboid {
+                                center: Vec3::ZERO,
+                                half_extents: Vec3::splat(4f32),
+                            },
+                            Plane {
+                                normal: Vec3::Y,
+                                offset: 2f32,
+                            },
+                        ],
+                    }),
+                    subtract: Box::new(Capsule {
+                        start: Vec3::new(-4f32, 0f32, 0f32),
+                        end: Vec3::new(4f32, 0f32, 0f32),
+                        radius: 1f32,
+                    }),
+                },
+                SmoothUnion {
+                    radius: 1f32,
+                    nodes: vec![
+                        Heightfield {
+                            noise: NoiseSettings {
+                                ground_level: -20f32,
+                                ..Default::default()
+                            },
+                        },
+                        Torus {
+                            center: Vec3::new(0f32, 8f32, 0f32),
+                            major_radius: 3f32,
+                            minor_radius: 1f32,
+                        },
+                    ],
+                },
+                Translate {
+                    offset: Vec3::new(20f32, 0f32, 20f32),
+                    node: Box::new(Rotate {
+                        rotation: Quat::from_rotation_y(0.5f32),
+                        node: Box::new(Scale {
+                            factor: 2f32,
+                            node: Box::new(Repeat {
+                                period: Vec3::new(6f32, 0f32, 6f32),
+                                node: Box::new(Material {
+                                    material: MaterialId::Snow,
+                                    node: Box::new(sphere(Vec3::ZERO, 1f32)),
+                                }),
+                            }),
+                        }),
+                    }),
+                },
+            ],
+        },
+    }
+}
+
+#[test]
+fn graphs_round_trip_through_serde() {
+    for graph in [DensityGraph::default(), every_node()] {
+        let text = ron::to_string(&graph).unwrap();
+        assert_eq!(ron::from_str::<DensityGraph>(&text).unwrap(), graph);
+    }
+}
+
+#[test]
+fn compiled_nodes_sample_their_shapes() {
+    let density = every_node().build(0).unwrap();
+    let sample = |pos: Vec3| density.0.sample_material(pos);
+
+    // Carved out of the box by the capsule
+    assert!(sample(Vec3::new(0f32, -2f32, 3f32)).0 < 0f32);
+    assert!(sample(Vec3::new(2f32, 0f32, 0f32)).0 > 0f32);
+    // Scaled sphere repeated along X and Z, its surface is twice as far from the center
+    for repeat in [
+        Vec3::ZERO,
+        Vec3::new(12f32, 0f32, 0f32),
+        Vec3::new(0f32, 0f32, -12f32),
+    ] {
+        let center = Quat::from_rotation_y(0.5f32) * repeat + Vec3::new(20f32, 0f32, 20f32);
+        let (value, material) = sample(center);
+        assert!((value + 2f32).abs() < 1e-4, "{value}");
+        assert_eq!(material, Some(MaterialId::Snow));
+    }
+}
+
+#[test]
+fn smooth_union_samples_the_same_value_with_materials() {
+    let graph = DensityNode::SmoothUnion {
+        radius: 2f32,
+        nodes: vec![
+            sphere(Vec3::ZERO, 2f32),
+            DensityNode::Material {
+                material: MaterialId::Sand,
+                node: Box::new(sphere(Vec3::new(3f32, 0f32, 0f32), 2f32)),
+            },
+        ],
+    };
+    let density = graph.compile(0).unwrap();
+    for x in 0..=6 {
+        let pos = Vec3::new(x as f32 * 0.5f32, 0.5f32, 0f32);
+        let (value, material) = density.sample_material(pos);
+        assert_eq!(value, density.sample(pos));
+        let expected = (pos.x > 1.5f32).then_some(MaterialId::Sand);
+        assert_eq!(material, expected, "{pos}");
+    }
+}
+
+#[test]
+fn scale_factor_has_to_be_positive() {
+    for factor in [0f32, -1f32, f32::NAN] {
+        let graph = DensityGraph {
+            root: DensityNode::Union {
+                nodes: vec![
+                    sphere(Vec3::ZERO, 1f32),
+                    DensityNode::Scale {
+                        factor,
+                        node: Box::new(sphere(Vec3::ZERO, 1f32)),
+                    },
+                ],
+            },
+        };
+        assert!(matches!(
+            graph.build(0),
+            Err(GraphError::InvalidScale(invalid)) if invalid.to_bits() == factor.to_bits()
+        ));
+        assert!(matches!(
+            DensitySettings::Graph(graph).build(0),
+            Err(DensityError::Graph(GraphError::InvalidScale(_)))
+        ));
+    }
+}
+
+#[test]
+fn smooth_union_ignores_node_order() {
+    let nodes = [
+        sphere(Vec3::ZERO, 2f32),
+        sphere(Vec3::new(2.5f32, 0f32, 0f32), 1.5f32),
+        sphere(Vec3::new(1f32, 2f32, 0f32), 1f32),
+    ];
+    let union = |order: [usize; 3]| {
+        DensityNode::SmoothUnion {
+            radius: 2f32,
+            nodes: order.map(|i| nodes[i].clone()).to_vec(),
+        }
+        .compile(0)
+        .unwrap()
+    };
+    let first = union([0, 1, 2]);
+    for order in [[0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+        let other = union(order);
+        for x in -4..=8 {
+            for y in -4..=8 {
+                let pos = Vec3::new(x as f32, y as f32, 0.5f32) * 0.5f32;
+                let (a, b) = (first.sample(pos), other.sample(pos));
+                assert!((a - b).abs() < 1e-5, "{order:?} at {pos}: {a} != {b}");
+            }
+        }
+    }
+    // Blending only ever adds to the union
+    let pos = Vec3::new(1.25f32, 0f32, 0f32);
+    assert!(first.sample(pos) < (pos.length() - 2f32).min(1.25f32 - 1.5f32));
+}
@@ -0,0 +1,307 @@
+use bevy::{prelude::*, render::mesh::Indices, utils::HashMap};
+use terrain_procgen::generation::{
+    mesh_chunk, DensityFunction, MaterialRules, MesherKind, TerrainChunk, TerrainGeneratorConfig,
+};
+
+const SIZE: i32 = 8;
+
+/// Arbitrary values at the grid points inside of the chunk, empty at its border
+/// so that the surface has to be closed
+struct GridValues<F>(F);
+
+impl<F: Fn(IVec3) -> f32 + Send + Sync> DensityFunction for GridValues<F> {
+    fn sample(&self, pos: Vec3) -> f32 {
+        let idx = pos.round().as_ivec3();
+        if idx.cmple(IVec3::ZERO).any() || idx.cmpge(IVec3::splat(SIZE)).any() {
+            1f32
+        } else {
+            (self.0)(idx)
+        }
+    }
+}
+
+fn random(idx: IVec3) -> f32 {
+    let mut hash = (idx.x as u32).wrapping_mul(0x8da6b343)
+        ^ (idx.y as u32).wrapping_mul(0xd8163841)
+        ^ (idx.z as u32).wrapping_mul(0xcb1ab31f);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b3c6d);
+    hash ^= hash >> 12;
+    (hash & 0xffff) as f32 / 0x8000 as f32 - 1f32
+}
+
+fn triangles(mesher: MesherKind, values: impl Fn(IVec3) -> f32 + Send + Sync) -> Vec<u32> {
+    let density = GridValues(values);
+    let mut chunk = TerrainChunk::new(IVec3::ZERO, UVec3::splat(SIZE as u32), 1f32);
+    chunk.sample(&density, &MaterialRules::default());
+    let config = TerrainGeneratorConfig {
+        mesher,
+        ..Default::default()
+    };
+    match mesh_chunk(&chunk, &config, None).indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|&idx| idx as u32).collect(),
+        Some(Indices::U32(indices)) => indices.clone(),
+        None => vec![],
+    }
+}
+
+/// Directed edges without exactly one matching edge going the opposite way,
+/// zero for a closed and consistently oriented mesh
+fn open_edges(indices: &[u32]) -> usize {
+    let mut edges = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            *edges.entry((triangle[a], triangle[b])).or_insert(0) += 1;
+        }
+    }
+    edges
+        .iter()
+        .filter(|&(&(a, b), &uses)| uses != 1 || edges.get(&(b, a)) != Some(&1))
+        .count()
+}
+
+/// Values of a single cube at the chunk's center, `values[x + 2 y + 4 z]` is at the vertex
+/// `(x, y, z)`. The rest of the grid is empty
+fn cube_triangles(values: [f32; 8]) -> Vec<u32> {
+    triangles(MesherKind::MarchingCubes33, move |idx| {
+        let offset = idx - IVec3::splat(SIZE / 2 - 1);
+        if offset.cmplt(IVec3::ZERO).any() || offset.cmpgt(IVec3::ONE).any() {
+            1f32
+        } else {
+            values[(offset.x + 2 * offset.y + 4 * offset.z) as usize]
+        }
+    })
+}
+
+/// Connected solid parts of the trilinear interpolation of the cube's values
+/// and their Euler characteristic, found on a fine grid
+fn solid_topology(values: [f32; 8]) -> (usize, i32) {
+    const STEPS: usize = 48;
+    const N: usize = STEPS + 1;
+    let mut solid = vec![false; N * N * N];
+    for (idx, solid) in solid.iter_mut().enumerate() {
+        let t = Vec3::new(
+            (idx % N) as f32,
+            (idx / N % N) as f32,
+            (idx / (N * N)) as f32,
+        ) / STEPS as f32;
+        let mut value = 0f32;
+        for (i, corner) in values.iter().enumerate() {
+            let weight = |bit: usize, t: f32| if i & bit == 0 { 1f32 - t } else { t };
+            value += corner * weight(1, t.x) * weight(2, t.y) * weight(4, t.z);
+        }
+        *solid = value < 0f32;
+    }
+    let at = |x: usize, y: usize, z: usize| solid[x + N * (y + N * z)];
+
+    // Solid grid points, with the edges, squares and cubes between them
+    let mut euler = 0;
+    for z in 0..N {
+        for y in 0..N {
+            for x in 0..N {
+                for dz in 0..=(z + 1 < N) as usize {
+                    for dy in 0..=(y + 1 < N) as usize {
+                        for dx in 0..=(x + 1 < N) as usize {
+                            let cell = (x..=x + dx)
+                                .all(|x| (y..=y + dy).all(|y| (z..=z + dz).all(|z| at(x, y, z))));
+                            if cell {
+                                euler += if (dx + dy + dz) % 2 == 0 { 1 } else { -1 };
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut visited = vec![false; N * N * N];
+    let mut parts = 0;
+    for start in 0..N * N * N {
+        if visited[start] || !solid[start] {
+            continue;
+        }
+        parts += 1;
+        visited[start] = true;
+        let mut stack = vec![start];
+        while let Some(idx) = stack.pop() {
+            let (x, y, z) = (idx % N, idx / N % N, idx / (N * N));
+            let neighbours = [
+                (x > 0).then(|| idx - 1),
+                (x + 1 < N).then(|| idx + 1),
+                (y > 0).then(|| idx - N),
+                (y + 1 < N).then(|| idx + N),
+                (z > 0).then(|| idx - N * N),
+                (z + 1 < N).then(|| idx + N * N),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if solid[next] && !visited[next] {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    (parts, euler)
+}
+
+/// Connected parts of the mesh
+fn mesh_parts(indices: &[u32]) -> usize {
+    fn find(parents: &mut HashMap<u32, u32>, idx: u32) -> u32 {
+        let parent = *parents.entry(idx).or_insert(idx);
+        if parent == idx {
+            return idx;
+        }
+        let root = find(parents, parent);
+        parents.insert(idx, root);
+        root
+    }
+
+    let mut parents = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        let root = find(&mut parents, triangle[0]);
+        for &idx in &triangle[1..] {
+            let other = find(&mut parents, idx);
+            parents.insert(other, root);
+        }
+    }
+    let vertices: Vec<u32> = parents.keys().copied().collect();
+    let mut roots: Vec<u32> = vertices
+        .into_iter()
+        .map(|idx| find(&mut parents, idx))
+        .collect();
+    roots.sort_unstable();
+    roots.dedup();
+    roots.len()
+}
+
+fn euler_characteristic(indices: &[u32]) -> i32 {
+    let mut vertices = indices.to_vec();
+    vertices.sort_unstable();
+    vertices.dedup();
+    let faces = indices.len() / 3;
+    // Every edge of a closed mesh is shared by two triangles
+    let edges = faces * 3 / 2;
+    vertices.len() as i32 - edges as i32 + faces as i32
+}
+
+#[test]
+fn random_field_is_watertight() {
+    let indices = triangles(MesherKind::MarchingCubes33, random);
+    assert!(!indices.is_empty());
+    assert_eq!(open_edges(&indices), 0);
+}
+
+#[test]
+fn checkerboard_field_is_watertight() {
+    // Every face of every cube is ambiguous
+    fn checkerboard(idx: IVec3) -> f32 {
+        let sign = if (idx.x + idx.y + idx.z) % 2 == 0 {
+            -1f32
+        } else {
+            1f32
+        };
+        sign * (1.5f32 + random(idx))
+    }
+
+    let indices = triangles(MesherKind::MarchingCubes33, checkerboard);
+    assert!(!indices.is_empty());
+    assert_eq!(open_edges(&indices), 0);
+}
+
+#[test]
+fn tunnel_joins_opposite_corners() {
+    // Interpolation stays solid along the diagonal between the corners
+    fn corners(idx: IVec3) -> f32 {
+        if idx == IVec3::splat(3) || idx == IVec3::splat(4) {
+            -1f32
+        } else {
+            0.1f32
+        }
+    }
+
+    let indices = triangles(MesherKind::MarchingCubes33, corners);
+    assert_eq!(open_edges(&indices), 0);
+    // A single closed surface
+    assert_eq!(euler_characteristic(&indices), 2);
+
+    // The original table always separates the corners
+    let indices = triangles(MesherKind::MarchingCubes, corners);
+    assert_eq!(euler_characteristic(&indices), 4);
+}
+
+#[test]
+fn opposite_corners_without_tunnel_stay_separate() {
+    fn corners(idx: IVec3) -> f32 {
+        if idx == IVec3::splat(3) || idx == IVec3::splat(4) {
+            -1f32
+        } else {
+            1f32
+        }
+    }
+
+    let indices = triangles(MesherKind::MarchingCubes33, corners);
+    assert_eq!(open_edges(&indices), 0);
+    // Two closed surfaces
+    assert_eq!(euler_characteristic(&indices), 4);
+}
+
+#[test]
+fn ambiguous_cubes_match_the_trilinear_interpolation() {
+    // Values are at the vertices `x + 2 y + 4 z`, negative ones are solid
+    let cases = [
+        // An edge and a corner split by the face, joined by a solid tunnel
+        (
+            "6.1.2",
+            [-0.886, -0.13, 0.056, 0.269, 0.085, 0.109, 0.229, -0.207],
+        ),
+        // Three corners joined on all faces, around an empty corner without a tunnel
+        (
+            "7.4.1",
+            [0.004, -0.078, -0.898, 0.015, -0.995, 0.016, 0.019, 0.005],
+        ),
+        // The same corners with an empty tunnel through the middle, a solid ring
+        (
+            "7.4.2",
+            [0.204, -0.098, -0.224, 0.062, -0.202, 0.069, 0.071, 0.366],
+        ),
+        // Two empty edges split by both faces, joined by an empty tunnel
+        (
+            "10.1.2",
+            [-0.1, 0.086, 0.039, -0.104, -0.095, 0.022, 0.472, -0.123],
+        ),
+        // Empty corners split by two adjacent faces, joined by an empty tunnel
+        (
+            "12.1.2",
+            [0.204, -0.098, -0.224, -0.019, -0.202, 0.069, 0.071, 0.366],
+        ),
+        // Three contours, an empty tunnel joins two of them
+        (
+            "13",
+            [-0.112, 0.123, 0.098, -0.115, 0.354, -0.036, -0.369, 0.302],
+        ),
+    ];
+
+    for (case, values) in cases {
+        let indices = cube_triangles(values);
+        assert_eq!(open_edges(&indices), 0, "case {case}");
+        let (parts, euler) = solid_topology(values);
+        assert_eq!(mesh_parts(&indices), parts, "case {case}");
+        // The surface of a solid part has twice its Euler characteristic
+        assert_eq!(euler_characteristic(&indices), 2 * euler, "case {case}");
+    }
+}
+
+#[test]
+fn non_finite_values_dont_panic() {
+    for special in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 0f32, -0f32] {
+        triangles(MesherKind::MarchingCubes33, move |idx| {
+            if (idx.x + 2 * idx.y + 3 * idx.z) % 5 == 0 {
+                special
+            } else {
+                random(idx)
+            }
+        });
+        // Ambiguous faces whose asymptotic decider compares NaN products
+        cube_triangles([-1f32, special, special, -1f32, 1f32, -1f32, -1f32, 1f32]);
+    }
+}
@@ -0,0 +1,256 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use terrain_procgen::generation::{
+    generate_chunks,
+    graph::DensityNode,
+    material::ATTRIBUTE_MATERIAL_ID,
+    mesh_chunk,
+    noise::{NoiseDensity, NoiseDimensions, Simplex},
+    triplanar::{ATTRIBUTE_SPLAT_WEIGHTS, ATTRIBUTE_TRIPLANAR_WEIGHTS},
+    MaterialId, MaterialRules, MesherKind, NormalMode, SplatSettings, TerrainChunk,
+    TerrainGeneratorConfig,
+};
+
+fn noise(size: u32) -> NoiseDensity<Simplex> {
+    NoiseDensity {
+        noise: Simplex::new(7),
+        dimensions: NoiseDimensions::Volume,
+        frequency: 0.3f32,
+        amplitude: 64f32,
+        ground_level: size as f32 / 2f32,
+    }
+}
+
+fn noisy_chunk(size: u32) -> TerrainChunk {
+    let mut chunk = TerrainChunk::new(IVec3::ZERO, UVec3::splat(size), 1f32);
+    chunk.sample(&noise(size), &MaterialRules::default());
+    chunk
+}
+
+fn positions_and_normals(mesh: &Mesh) -> Vec<(Vec3, Vec3)> {
+    let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap();
+    let normals = mesh.attribute(Mesh::ATTRIBUTE_NORMAL).unwrap();
+    positions
+        .as_float3()
+        .unwrap()
+        .iter()
+        .zip(normals.as_float3().unwrap())
+        .map(|(position, normal)| (Vec3::from(*position), Vec3::from(*normal)))
+        .collect()
+}
+
+#[test]
+fn large_chunk_uses_u32_indices_in_bounds() {
+    let config = TerrainGeneratorConfig::default();
+    let mesh = mesh_chunk(&noisy_chunk(64), &config, None);
+
+    let vertex_count = mesh.count_vertices();
+    assert!(vertex_count > u16::MAX as usize + 1);
+    match mesh.indices() {
+        Some(Indices::U32(indices)) => {
+            assert!(indices.iter().all(|&idx| (idx as usize) < vertex_count))
+        }
+        indices => panic!("expected 32 bit indices, got {indices:?}"),
+    }
+}
+
+#[test]
+fn small_chunk_uses_u16_indices() {
+    let config = TerrainGeneratorConfig::default();
+    let mesh = mesh_chunk(&noisy_chunk(8), &config, None);
+
+    let vertex_count = mesh.count_vertices();
+    match mesh.indices() {
+        Some(Indices::U16(indices)) => {
+            assert!(indices.iter().all(|&idx| (idx as usize) < vertex_count))
+        }
+        indices => panic!("expected 16 bit indices, got {indices:?}"),
+    }
+}
+
+#[test]
+fn border_normals_match_between_neighbours() {
+    let size = 8;
+    // Keep the surface off the grid points, where several vertices would overlap
+    let density = NoiseDensity {
+        ground_level: 4.3f32,
+        ..noise(size)
+    };
+    for mesher in [
+        MesherKind::MarchingCubes,
+        MesherKind::MarchingCubes33,
+        MesherKind::MarchingTetrahedra,
+        MesherKind::SurfaceNets,
+        MesherKind::DualContouring,
+    ] {
+        let config = TerrainGeneratorConfig {
+            mesher,
+            normals: NormalMode::FaceWeighted,
+            ..Default::default()
+        };
+        let [left, right] = [IVec3::ZERO, IVec3::X].map(|coord| {
+            let mut chunk = TerrainChunk::new(coord, UVec3::splat(size), 1f32);
+            chunk.sample(&density, &MaterialRules::default());
+            positions_and_normals(&mesh_chunk(&chunk, &config, Some(&density)))
+        });
+
+        let mut shared = 0;
+        for (position, normal) in &left {
+            let Some((_, right_normal)) = right
+                .iter()
+                .find(|(right_position, _)| right_position.distance(*position) < 1e-4f32)
+            else {
+                continue;
+            };
+            shared += 1;
+            assert!(
+                normal.distance(*right_normal) < 1e-4f32,
+                "{mesher:?} normals differ at {position}: {normal} and {right_normal}"
+            );
+        }
+        assert!(shared > 0, "{mesher:?} chunks share no vertices");
+    }
+}
+
+#[test]
+fn shared_points_get_the_same_material() {
+    let size = 8;
+    let density = noise(size);
+    let rules = MaterialRules::default();
+    let [left, right] = [IVec3::ZERO, IVec3::X].map(|coord| {
+        let mut chunk = TerrainChunk::new(coord, UVec3::splat(size), 1f32);
+        chunk.sample(&density, &rules);
+        chunk
+    });
+
+    // Layers of points the chunks share, meshers read materials from one layer into the padding
+    let mut materials = vec![];
+    for x in size as i32 - 1..=size as i32 + 1 {
+        for y in -1..=size as i32 + 1 {
+            for z in -1..=size as i32 + 1 {
+                let pos = IVec3::new(x, y, z).as_vec3();
+                let material = left.material_at(pos).unwrap();
+                assert_eq!(material, right.material_at(pos).unwrap(), "{pos}");
+                materials.push(material);
+            }
+        }
+    }
+    assert!(materials.contains(&MaterialId::Rock));
+    assert!(materials
+        .iter()
+        .any(|&material| material != MaterialId::Rock));
+}
+
+#[test]
+fn cliffs_and_plains_get_different_materials() {
+    // Flat ground with a tall block standing on it and a sand tagged block
+    let density = DensityNode::Union {
+        nodes: vec![
+            DensityNode::Plane {
+                normal: Vec3::Y,
+                offset: 2.5f32,
+            },
+            DensityNode::Cuboid {
+                center: Vec3::new(4f32, 4f32, 4f32),
+                half_extents: Vec3::new(1.5f32, 4f32, 1.5f32),
+            },
+            DensityNode::Material {
+                material: MaterialId::Sand,
+                node: Box::new(DensityNode::Sphere {
+                    center: Vec3::new(12f32, 2.5f32, 12f32),
+                    radius: 2.5f32,
+                }),
+            },
+        ],
+    }
+    .compile(0)
+    .unwrap();
+    let mut chunk = TerrainChunk::new(IVec3::ZERO, UVec3::splat(16), 1f32);
+    chunk.sample(density.as_ref(), &MaterialRules::default());
+    let mesh = mesh_chunk(
+        &chunk,
+        &TerrainGeneratorConfig::default(),
+        Some(density.as_ref()),
+    );
+
+    let Some(VertexAttributeValues::Uint32(materials)) = mesh.attribute(ATTRIBUTE_MATERIAL_ID)
+    else {
+        panic!("mesh has no material ids");
+    };
+    let positions = positions_and_normals(&mesh);
+    let material_at = |position: Vec3| {
+        let (idx, _) = positions
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.0.distance(position).total_cmp(&b.0.distance(position)))
+            .unwrap();
+        materials[idx]
+    };
+    assert_eq!(
+        material_at(Vec3::new(10f32, 2.5f32, 4f32)),
+        MaterialId::Grass as u32
+    );
+    assert_eq!(
+        material_at(Vec3::new(2.5f32, 5f32, 4f32)),
+        MaterialId::Rock as u32
+    );
+    assert_eq!(
+        material_at(Vec3::new(12f32, 5f32, 12f32)),
+        MaterialId::Sand as u32
+    );
+}
+
+#[test]
+fn splatting_adds_normalized_weights() {
+    let config = TerrainGeneratorConfig {
+        splatting: SplatSettings {
+            enabled: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mesh = mesh_chunk(&noisy_chunk(8), &config, None);
+
+    let Some(VertexAttributeValues::Float32x3(triplanar)) =
+        mesh.attribute(ATTRIBUTE_TRIPLANAR_WEIGHTS)
+    else {
+        panic!("mesh has no triplanar weights");
+    };
+    let Some(VertexAttributeValues::Float32x4(splat)) = mesh.attribute(ATTRIBUTE_SPLAT_WEIGHTS)
+    else {
+        panic!("mesh has no splat weights");
+    };
+    assert_eq!(triplanar.len(), mesh.count_vertices());
+    assert_eq!(splat.len(), mesh.count_vertices());
+    // Every material is covered by one of the default layers
+    for sum in triplanar
+        .iter()
+        .map(|weights| weights.iter().sum::<f32>())
+        .chain(splat.iter().map(|weights| weights.iter().sum::<f32>()))
+    {
+        assert!((sum - 1f32).abs() < 1e-5f32, "weights sum up to {sum}");
+    }
+}
+
+#[test]
+fn generate_chunks_matches_meshing_one_by_one() {
+    let config = TerrainGeneratorConfig {
+        chunks_amount: UVec3::new(3, 1, 2),
+        chunk_size: UVec3::splat(8),
+        ..Default::default()
+    };
+    let density = noise(8);
+    let chunks = generate_chunks(&config, &density);
+    assert_eq!(chunks.len(), 6);
+
+    for (chunk, mesh) in &chunks {
+        let mut expected = TerrainChunk::new(chunk.coord(), config.chunk_size, 1f32);
+        expected.sample(&density, &config.materials);
+        assert_eq!(
+            positions_and_normals(mesh),
+            positions_and_normals(&mesh_chunk(&expected, &config, Some(&density)))
+        );
+    }
+}
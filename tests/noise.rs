@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use terrain_procgen::generation::noise::{
+    Fractal, FractalKind, FractalSettings, NoiseFn, NoiseKind, Simplex,
+};
+
+const KINDS: [NoiseKind; 4] = [
+    NoiseKind::Perlin,
+    NoiseKind::Simplex,
+    NoiseKind::Value,
+    NoiseKind::Worley,
+];
+
+fn grid() -> impl Iterator<Item = Vec3> {
+    (0..64).map(|i| Vec3::new(i as f32 * 0.37f32, (i % 7) as f32 * 1.3f32, (i / 8) as f32))
+}
+
+#[test]
+fn turbulence_differs_from_billow() {
+    let billow = Fractal::billow(Simplex::new(7));
+    let turbulence = Fractal::turbulence(Simplex::new(7));
+
+    let differing = grid()
+        .filter(|&p| billow.sample_3d(p) != turbulence.sample_3d(p))
+        .count();
+    assert!(differing > 0);
+    // Without recentering the creases stay at zero
+    assert!(grid().all(|p| (0f32..=1f32).contains(&turbulence.sample_3d(p))));
+}
+
+#[test]
+fn ridged_stays_in_range() {
+    for ridge_offset in [0.25f32, 1f32, 2f32] {
+        let ridged = Fractal::new(
+            Simplex::new(7),
+            FractalSettings {
+                kind: FractalKind::Ridged,
+                ridge_offset,
+                ..Default::default()
+            },
+        );
+        let samples: Vec<_> = (0..4096)
+            .map(|i| {
+                let p = Vec3::new((i % 16) as f32, (i / 16 % 16) as f32, (i / 256) as f32);
+                ridged.sample_3d(p * 0.173f32)
+            })
+            .collect();
+        for value in &samples {
+            assert!((-1f32..=1f32).contains(value), "{ridge_offset}: {value}");
+        }
+        // Spread over the range rather than squeezed into a corner of it
+        let (min, max) = samples
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+        assert!(max - min > 0.5f32, "{ridge_offset}: {min} to {max}");
+    }
+}
+
+#[test]
+fn samples_are_pinned() {
+    // Samples at the same points in 2, 3 and 4 dimensions with seed 42,
+    // changing them changes every saved seed's terrain
+    let expected = [
+        [-0.6709019, -0.3047207, 0.24092568],
+        [0.83549446, -0.31048146, -0.7552848],
+        [0.077485204, -0.61006325, -0.09685794],
+        [-0.32487994, 0.27885842, 0.37710857],
+    ];
+    for (kind, expected) in KINDS.into_iter().zip(expected) {
+        let noise = kind.build(42);
+        let samples = [
+            noise.sample_2d(Vec2::new(1.3f32, -2.7f32)),
+            noise.sample_3d(Vec3::new(1.3f32, -2.7f32, 0.45f32)),
+            noise.sample_4d(Vec4::new(1.3f32, -2.7f32, 0.45f32, 3.1f32)),
+        ];
+        assert_eq!(samples, expected, "{kind:?}");
+    }
+}
+
+#[test]
+fn seeds_change_the_samples() {
+    for kind in KINDS {
+        let (first, second) = (kind.build(1), kind.build(2));
+        let differing = |sample: &dyn Fn(&dyn NoiseFn, Vec3) -> f32| {
+            grid()
+                .filter(|&p| sample(first.as_ref(), p) != sample(second.as_ref(), p))
+                .count()
+        };
+        let in_2d = differing(&|noise, p| noise.sample_2d(p.truncate()));
+        let in_3d = differing(&|noise, p| noise.sample_3d(p));
+        let in_4d = differing(&|noise, p| noise.sample_4d(p.extend(0.5f32)));
+        for differing in [in_2d, in_3d, in_4d] {
+            assert!(differing > grid().count() / 2, "{kind:?}");
+        }
+    }
+}
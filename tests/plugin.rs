@@ -0,0 +1,155 @@
+use std::{thread, time::Duration};
+
+use bevy::{gizmos::GizmoPlugin, prelude::*};
+use terrain_procgen::generation::{
+    density::Sphere, ChunkMap, DensitySettings, HistoryCommand, MarchingCubesTerrain, TerrainChunk,
+    TerrainDensity, TerrainEdit, TerrainGeneratorConfig, TerrainViewer, MAX_VIEW_RADIUS,
+};
+
+/// App with the terrain plugin, `setup` runs before the plugins are finished
+fn app(setup: impl FnOnce(&mut App)) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .add_asset::<Mesh>()
+        .add_asset::<Image>()
+        .add_asset::<Shader>()
+        .add_asset::<StandardMaterial>()
+        .add_plugins((GizmoPlugin, MarchingCubesTerrain));
+    setup(&mut app);
+    app.finish();
+    app
+}
+
+#[test]
+fn game_density_is_kept() {
+    let app = app(|app| {
+        app.insert_resource(TerrainDensity::new(Sphere {
+            center: Vec3::ZERO,
+            radius: 3f32,
+        }));
+    });
+    let config = app.world.resource::<TerrainGeneratorConfig>();
+    assert_eq!(config.density, DensitySettings::Custom);
+}
+
+#[test]
+fn game_config_is_kept() {
+    let app = app(|app| {
+        app.insert_resource(TerrainDensity::default())
+            .insert_resource(TerrainGeneratorConfig {
+                seed: 7,
+                ..default()
+            });
+    });
+    let config = app.world.resource::<TerrainGeneratorConfig>();
+    assert_eq!(config.density, DensitySettings::Plane);
+    assert_eq!(config.seed, 7);
+}
+
+#[test]
+fn built_in_density_by_default() {
+    let app = app(|_| {});
+    let config = app.world.resource::<TerrainGeneratorConfig>();
+    assert_eq!(config.density, DensitySettings::Plane);
+    assert!(app.world.contains_resource::<TerrainDensity>());
+}
+
+#[test]
+fn streaming_handles_large_radii_and_distances() {
+    let mut app = app(|app| {
+        app.insert_resource(TerrainGeneratorConfig {
+            streaming: true,
+            view_radius: u32::MAX,
+            chunks_per_frame: 4,
+            ..default()
+        });
+    });
+    let viewer = app
+        .world
+        .spawn((TerrainViewer::default(), GlobalTransform::IDENTITY))
+        .id();
+    app.update();
+    let chunks = app.world.resource::<ChunkMap>().0.len();
+    assert!(chunks > 0 && chunks <= 4);
+
+    // Chunks at the origin are too far from the viewer to square their offsets in `i32`
+    app.world
+        .entity_mut(viewer)
+        .insert(GlobalTransform::from_xyz(1e9f32, 0f32, 0f32));
+    app.update();
+    let chunk_map = app.world.resource::<ChunkMap>();
+    let radius = MAX_VIEW_RADIUS as i32;
+    assert!(chunk_map.0.keys().all(|coord| coord.x > radius));
+}
+
+/// Update until the chunk at `coord` has a mesh
+fn wait_for_mesh(app: &mut App, coord: IVec3) -> Entity {
+    for _ in 0..1000 {
+        app.update();
+        if let Some(&entity) = app.world.resource::<ChunkMap>().0.get(&coord) {
+            if app.world.entity(entity).contains::<Handle<Mesh>>() {
+                return entity;
+            }
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("chunk at {coord} wasn't meshed");
+}
+
+#[test]
+fn streaming_keeps_edited_chunks() {
+    let mut app = app(|app| {
+        app.insert_resource(TerrainGeneratorConfig {
+            streaming: true,
+            view_radius: 1,
+            chunks_per_frame: 27,
+            ..default()
+        });
+    });
+    let viewer = app
+        .world
+        .spawn((TerrainViewer::default(), GlobalTransform::IDENTITY))
+        .id();
+    let move_viewer = |app: &mut App, translation: Vec3| {
+        app.world
+            .entity_mut(viewer)
+            .insert(GlobalTransform::from_translation(translation));
+    };
+    let value_at = |app: &mut App, pos: Vec3| {
+        let entity = wait_for_mesh(app, IVec3::ZERO);
+        let chunk = app.world.get::<TerrainChunk>(entity).unwrap();
+        chunk.value_at(pos).unwrap()
+    };
+
+    let center = Vec3::new(2f32, 0f32, 2f32);
+    let sampled = value_at(&mut app, center);
+    app.world.send_event(TerrainEdit {
+        center,
+        radius: 1.5f32,
+        strength: 2f32,
+        mode: default(),
+    });
+    app.update();
+    let edited = value_at(&mut app, center);
+    assert_ne!(edited, sampled);
+
+    // Out of range and back again
+    let far_away = Vec3::new(100f32, 0f32, 0f32);
+    move_viewer(&mut app, far_away);
+    app.update();
+    assert!(!app
+        .world
+        .resource::<ChunkMap>()
+        .0
+        .contains_key(&IVec3::ZERO));
+    move_viewer(&mut app, Vec3::ZERO);
+    assert_eq!(value_at(&mut app, center), edited);
+
+    // Undone while out of range
+    move_viewer(&mut app, far_away);
+    app.update();
+    app.world.send_event(HistoryCommand::Undo);
+    app.update();
+    move_viewer(&mut app, Vec3::ZERO);
+    assert_eq!(value_at(&mut app, center), sampled);
+}
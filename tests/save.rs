@@ -0,0 +1,172 @@
+use std::io::Write;
+
+use bevy::prelude::*;
+use flate2::{write::ZlibEncoder, Compression};
+use terrain_procgen::generation::{
+    mesh_chunk,
+    noise::{NoiseDensity, NoiseDimensions, Simplex},
+    save::{read_world, write_world, WorldFileError, WORLD_FORMAT_VERSION},
+    BrushMode, MaterialRules, TerrainChunk, TerrainEdit, TerrainGeneratorConfig,
+};
+
+const SIZE: u32 = 8;
+
+fn edited_chunks(config: &TerrainGeneratorConfig) -> Vec<TerrainChunk> {
+    let noise = NoiseDensity {
+        noise: Simplex::new(config.seed),
+        dimensions: NoiseDimensions::Surface,
+        frequency: 0.1f32,
+        amplitude: 4f32,
+        ground_level: SIZE as f32 / 2f32,
+    };
+    let edit = TerrainEdit {
+        center: Vec3::new(SIZE as f32, SIZE as f32 / 2f32, 4f32),
+        radius: 3f32,
+        strength: -2f32,
+        mode: BrushMode::Sphere,
+    };
+    [(IVec3::ZERO, 0), (IVec3::X, 1)]
+        .into_iter()
+        .map(|(coord, lod)| {
+            let mut chunk =
+                TerrainChunk::with_lod(coord, config.chunk_size, config.cube_edge_length, lod);
+            chunk.sample(&noise, &MaterialRules::default());
+            chunk.apply_edit(&edit);
+            chunk
+        })
+        .collect()
+}
+
+#[test]
+fn saved_world_meshes_the_same() {
+    let config = TerrainGeneratorConfig {
+        chunk_size: UVec3::splat(SIZE),
+        lod_levels: 1,
+        seed: 42,
+        ..default()
+    };
+    let chunks = edited_chunks(&config);
+
+    let mut bytes = Vec::new();
+    write_world(&mut bytes, &config, &chunks).unwrap();
+    let world = read_world(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(world.config, config);
+    assert_eq!(world.chunks.len(), chunks.len());
+    for (saved, loaded) in chunks.iter().zip(&world.chunks) {
+        assert_eq!(saved.coord(), loaded.coord());
+        assert_eq!(saved.lod(), loaded.lod());
+        let positions = |mesh: &Mesh| {
+            let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap();
+            positions.as_float3().unwrap().to_vec()
+        };
+        assert_eq!(
+            positions(&mesh_chunk(saved, &config, None)),
+            positions(&mesh_chunk(loaded, &world.config, None))
+        );
+    }
+}
+
+#[test]
+fn other_versions_and_files_are_rejected() {
+    let config = TerrainGeneratorConfig::default();
+    let mut bytes = Vec::new();
+    write_world(&mut bytes, &config, []).unwrap();
+
+    bytes[4..8].copy_from_slice(&(WORLD_FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        read_world(&mut bytes.as_slice()),
+        Err(WorldFileError::UnsupportedVersion(version)) if version == WORLD_FORMAT_VERSION + 1
+    ));
+
+    assert!(matches!(
+        read_world(&mut b"not a world".as_slice()),
+        Err(WorldFileError::NotAWorld)
+    ));
+}
+
+/// A saved world and the offset of its first chunk
+fn world_bytes() -> (Vec<u8>, usize) {
+    let config = TerrainGeneratorConfig {
+        chunk_size: UVec3::splat(SIZE),
+        lod_levels: 1,
+        ..default()
+    };
+    let mut bytes = Vec::new();
+    write_world(&mut bytes, &config, &edited_chunks(&config)).unwrap();
+    let config_len = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+    (bytes, 16 + config_len + 4)
+}
+
+#[test]
+fn truncated_files_are_rejected() {
+    let (bytes, _) = world_bytes();
+    for len in 4..bytes.len() {
+        assert!(
+            matches!(
+                read_world(&mut &bytes[..len]),
+                Err(WorldFileError::Truncated | WorldFileError::ChunkSize { .. })
+            ),
+            "truncated at {len}"
+        );
+    }
+}
+
+#[test]
+fn corrupted_headers_are_rejected() {
+    let (bytes, first_chunk) = world_bytes();
+    let corrupt = |offset: usize, value: u32| {
+        let mut bytes = bytes.clone();
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        read_world(&mut bytes.as_slice())
+    };
+
+    assert!(matches!(
+        corrupt(12, u32::MAX),
+        Err(WorldFileError::Config(_))
+    ));
+    assert!(matches!(
+        corrupt(first_chunk - 4, u32::MAX),
+        Err(WorldFileError::TooManyChunks(_))
+    ));
+    // More chunks than the file holds
+    assert!(matches!(
+        corrupt(first_chunk - 4, 3),
+        Err(WorldFileError::Truncated)
+    ));
+    for lod in [2, 40, u32::MAX] {
+        assert!(matches!(
+            corrupt(first_chunk + 12, lod),
+            Err(WorldFileError::InvalidLod { lod: invalid, .. }) if invalid == lod
+        ));
+    }
+    assert!(matches!(
+        corrupt(first_chunk + 16, u32::MAX),
+        Err(WorldFileError::ChunkSize { .. })
+    ));
+    // The first chunk was saved with the full level of detail, so it inflates to too few points
+    assert!(matches!(
+        corrupt(first_chunk + 12, 1),
+        Err(WorldFileError::ChunkSize { .. })
+    ));
+}
+
+#[test]
+fn chunks_inflating_past_their_size_are_rejected() {
+    let (mut bytes, first_chunk) = world_bytes();
+    let compressed_len = u32::from_le_bytes(
+        bytes[first_chunk + 16..first_chunk + 20]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    // Zeros compress well, a few kilobytes inflate to megabytes
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&vec![0u8; 1 << 22]).unwrap();
+    let bomb = encoder.finish().unwrap();
+    bytes[first_chunk + 16..first_chunk + 20].copy_from_slice(&(bomb.len() as u32).to_le_bytes());
+    bytes.splice(first_chunk + 20..first_chunk + 20 + compressed_len, bomb);
+    assert!(matches!(
+        read_world(&mut bytes.as_slice()),
+        Err(WorldFileError::ChunkSize { .. })
+    ));
+}